@@ -0,0 +1,68 @@
+extern crate min_rs as min;
+use std::thread;
+use std::sync::mpsc::channel;
+
+fn main() {
+    let id: u8 = 0;
+    let tx_data: [u8; 8] = [0xaa, 0xaa, 0xaa, 0, 0, 0, 0, 1];
+    let (tx, rx) = channel();
+
+    let app1_builder = thread::Builder::new().name("app1".into());
+    let app1 = app1_builder.spawn(move || {
+        let uart1 = min::FnInterface::new(
+            || print!("uart1 send frame: [ "),
+            || println!("]"),
+            || 128,
+            |_port, byte| {
+                print!("0x{:02x} ", byte);
+                if let Err(e) = tx.send(byte) {
+                    println!("uart1: {}", e);
+                }
+            },
+        );
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            false,
+        );
+
+        match min1.send(id, tx_data) {
+            Ok(size) => {
+                println!("min1: {} bytes sent.", size);
+            },
+            Err(_) => panic!("Opps!"),
+        };
+    }).unwrap();
+
+    let app2_builder = thread::Builder::new().name("app2".into());
+    let app2 = app2_builder.spawn(move || {
+        let uart2 = min::FnInterface::new(|| {}, || {}, || 128, |_port, _byte| {});
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            false,
+        );
+
+        for byte in rx.iter() {
+            min2.poll(&[byte][0..1], 1);
+        }
+
+        match min2.get_msg() {
+            Ok(msg) => {
+                print!("app2 receive data: [ ");
+                for i in 0..msg.len {
+                    print!("0x{:02x} ", msg.buf[i as usize]);
+                }
+                println!("]");
+            },
+            Err(_) => {
+                println!("No msg!");
+            }
+        }
+    }).unwrap();
+
+    app1.join().unwrap();
+    app2.join().unwrap();
+}
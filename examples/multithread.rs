@@ -6,16 +6,14 @@ use env_logger;
 
 struct Uart {
     name: String,
-    tx_space_avaliable: u16,
     sender: Sender<u8>,
     receiver: Receiver<u8>,
 }
 
 impl Uart {
-    fn new(name: String, tx_space_avaliable: u16, sender: Sender<u8>, receiver: Receiver<u8>) -> Self {
+    fn new(name: String, sender: Sender<u8>, receiver: Receiver<u8>) -> Self {
         Uart{
             name: name,
-            tx_space_avaliable: tx_space_avaliable,
             sender: sender,
             receiver: receiver,
         }
@@ -29,10 +27,6 @@ impl Uart {
         println!("{}: Close uart.", self.name);
     }
 
-    fn available_for_write(&self) -> u16 {
-        self.tx_space_avaliable
-    }
-
     fn tx(&self, byte: u8) {
         print!("0x{:02x} ", byte);
         match self.sender.send(byte) {
@@ -52,9 +46,6 @@ impl min::Interface for Uart {
     fn tx_finished(&self) {
         println!("]");
     }
-    fn tx_space(&self) -> u16 {
-        self.available_for_write()
-    }
     
     fn tx_byte(&self, _port: u8, byte: u8) {
         self.tx(byte);
@@ -72,7 +63,7 @@ fn main() {
     
     let app1_builder = thread::Builder::new().name("app1".into());
     let app1 = app1_builder.spawn(move || {
-        let uart1 = Uart::new(String::from("uart1"), 128, tx1, rx1);
+        let uart1 = Uart::new(String::from("uart1"), tx1, rx1);
         let mut min1 = min::Context::new(
             String::from("min1"),
             &uart1,
@@ -87,9 +78,10 @@ fn main() {
         }
         println!(" ]");
 
-        match min1.send_frame(id, &tx_data, tx_data.len() as u8) {
+        match min1.send(id, tx_data) {
             Ok(size) => {
-                println!("{}: {} bytes sent.", min1.hw_if.name, size);
+                // The name of the context (log target) and the one of its interface are distinct
+                println!("{} on {}: {} bytes sent.", min1.name(), min1.hw_if.name, size);
             },
             Err(_) => panic!("Opps!"),
         };
@@ -99,7 +91,7 @@ fn main() {
     
     let app2_builder = thread::Builder::new().name("app2".into());
     let app2 = app2_builder.spawn(move || {
-        let uart2 = Uart::new(String::from("uart2"), 128, tx2, rx2);
+        let uart2 = Uart::new(String::from("uart2"), tx2, rx2);
         let mut min2 = min::Context::new(
             String::from("min2"),
             &uart2,
@@ -4,7 +4,6 @@ use log::LevelFilter;
 use env_logger;
 
 struct Uart {
-    tx_space_avaliable: u16,
     rx_buf: RefCell<[u8; 255]>,
     rx_buf_index: RefCell<u8>,
     loopback: bool,
@@ -19,10 +18,6 @@ impl Uart {
         println!("Close uart.");
     }
 
-    fn available_for_write(&self) -> u16 {
-        self.tx_space_avaliable
-    }
-
     fn tx(&self, byte: u8) {
         print!("0x{:02x} ", byte);
         if self.loopback {
@@ -48,9 +43,6 @@ impl min::Interface for Uart {
     fn tx_finished(&self) {
         println!("]");
     }
-    fn tx_space(&self) -> u16 {
-        self.available_for_write()
-    }
     
     fn tx_byte(&self, _port: u8, byte: u8) {
         self.tx(byte);
@@ -65,7 +57,6 @@ fn main() {
     let tx_data: [u8; 8] = [0xaa, 0xaa, 0xaa, 0, 0, 0, 0, 1];
     let rx_data: [u8; 255] = [0; 255];
     let uart = Uart{
-        tx_space_avaliable: 128,
         rx_buf: RefCell::new(rx_data),
         rx_buf_index: RefCell::new(0),
         loopback: true,
@@ -86,7 +77,7 @@ fn main() {
     println!(" ]");
 
     print!("MIN Frame: ");
-    let sent = match min.send_frame(id, &tx_data, tx_data.len() as u8) {
+    let sent = match min.send(id, tx_data) {
         Ok(size) => size,
         Err(_) => panic!("Opps!"),
     };
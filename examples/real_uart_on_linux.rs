@@ -17,16 +17,14 @@ const BAUD_RATE: serial::BaudRate = serial::Baud115200;
 struct Uart {
     port: RefCell<SystemPort>,
     name: String,
-    tx_space_avaliable: u16,
     output: Arc<Mutex<String>>,
 }
 
 impl Uart {
-    fn new(port: SystemPort, name: String, tx_space_avaliable: u16) -> Self {
+    fn new(port: SystemPort, name: String) -> Self {
         Uart{
             port: RefCell::new(port),
             name: name,
-            tx_space_avaliable: tx_space_avaliable,
             output: Arc::new(Mutex::new(String::from(""))),
         }
     }
@@ -45,10 +43,6 @@ impl Uart {
         debug!(target: self.name.as_str(), "{}: Open uart.", self.name);
     }
 
-    fn available_for_write(&self) -> u16 {
-        self.tx_space_avaliable
-    }
-
     fn tx(&self, byte: u8) {
         let mut output = self.output.lock().unwrap();
         output.push_str(format!("0x{:02x} ", byte).as_str());
@@ -82,9 +76,6 @@ impl min::Interface for Uart {
         output.push_str(format!("]").as_str());
         trace!(target: self.name.as_str(), "{}", output);
     }
-    fn tx_space(&self) -> u16 {
-        self.available_for_write()
-    }
     
     fn tx_byte(&self, _min_port: u8, byte: u8) {
         self.tx(byte);
@@ -96,7 +87,7 @@ fn main() {
     env_logger::init();
     let tx_data: [u8; 3] = [1, 2, 3];
     let port = serial::open(SERIAL_PORT).unwrap();
-    let uart = Uart::new(port, String::from("uart"), 128);
+    let uart = Uart::new(port, String::from("uart"));
     let mut min = min::Context::new(
         String::from("min"),
         &uart,
@@ -106,14 +97,11 @@ fn main() {
     min.hw_if.open();
 
     let mut buf: Vec<u8> = (0..255).collect();
-    min.reset_transport(true).unwrap_or(());
+    min.reset_transport(true).unwrap_or(0);
     min.queue_frame(0, &tx_data[..], tx_data.len() as u8).unwrap_or(());
     loop {
-        min.poll(&[0][0..0], 0);
-        if let Ok(n) = min.hw_if.read(&mut buf[..]) {
-            min.poll(&buf[0..n], n as u32);
-        };
-        if let Ok(msg) = min.get_msg() {
+        let n = min.hw_if.read(&mut buf[..]).unwrap_or(0);
+        for msg in min.process(&buf[0..n]) {
             if let Ok(string) = String::from_utf8(msg.buf[0..msg.len as usize].to_vec()) {
                 println!("get msg: {}", string);
             } else {
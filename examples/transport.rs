@@ -8,17 +8,15 @@ use env_logger;
 
 struct Uart {
     name: String,
-    tx_space_avaliable: u16,
     sender: Sender<u8>,
     receiver: Receiver<u8>,
     output: Arc<Mutex<String>>,
 }
 
 impl Uart {
-    fn new(name: String, tx_space_avaliable: u16, sender: Sender<u8>, receiver: Receiver<u8>) -> Self {
+    fn new(name: String, sender: Sender<u8>, receiver: Receiver<u8>) -> Self {
         Uart{
             name: name,
-            tx_space_avaliable: tx_space_avaliable,
             sender: sender,
             receiver: receiver,
             output: Arc::new(Mutex::new(String::from(""))),
@@ -33,10 +31,6 @@ impl Uart {
     //    debug!(target: self.name.as_str(), "{}: Close uart.", self.name);
     //}
 
-    fn available_for_write(&self) -> u16 {
-        self.tx_space_avaliable
-    }
-
     fn tx(&self, byte: u8) {
         let mut output = self.output.lock().unwrap();
         output.push_str(format!("0x{:02x} ", byte).as_str());
@@ -61,9 +55,6 @@ impl min::Interface for Uart {
         output.push_str(format!("]").as_str());
         trace!(target: self.name.as_str(), "{}", output);
     }
-    fn tx_space(&self) -> u16 {
-        self.available_for_write()
-    }
     
     fn tx_byte(&self, _port: u8, byte: u8) {
         self.tx(byte);
@@ -84,7 +75,7 @@ fn main() {
 
     let app1_builder = thread::Builder::new().name("app1".into());
     let app1 = app1_builder.spawn(move || {
-        let uart1 = Uart::new(String::from("uart1"), 128, tx1, rx1);
+        let uart1 = Uart::new(String::from("uart1"), tx1, rx1);
         let mut min1 = min::Context::new(
             String::from("min1"),
             &uart1,
@@ -97,11 +88,8 @@ fn main() {
         min1.queue_frame(id, &tx_data1, tx_data1.len() as u8).unwrap();
         min1.queue_frame(id, &tx_data1, tx_data1.len() as u8).unwrap();
         loop {
-            for byte in min1.hw_if.receiver.try_iter() {
-                min1.poll(&[byte as u8][0..1], 1);
-            }
-            min1.poll(&[0][0..0], 0);
-            if let Ok(msg) = min1.get_msg() {
+            let bytes: Vec<u8> = min1.hw_if.receiver.try_iter().collect();
+            for msg in min1.process(&bytes) {
                 print!("app1 receive data: [ ");
                 for i in 0..msg.len {
                     print!("0x{:02x} ", msg.buf[i as usize]);
@@ -116,7 +104,7 @@ fn main() {
     
     let app2_builder = thread::Builder::new().name("app2".into());
     let app2 = app2_builder.spawn(move || {
-        let uart2 = Uart::new(String::from("uart2"), 128, tx2, rx2);
+        let uart2 = Uart::new(String::from("uart2"), tx2, rx2);
         let mut min2 = min::Context::new(
             String::from("min2"),
             &uart2,
@@ -128,11 +116,8 @@ fn main() {
         min2.queue_frame(id, &tx_data2, tx_data2.len() as u8).unwrap();
         min2.queue_frame(id, &tx_data2, tx_data2.len() as u8).unwrap();
         loop {
-            for byte in min2.hw_if.receiver.try_iter() {
-                min2.poll(&[byte as u8][0..1], 1);
-            }
-            min2.poll(&[0][0..0], 0);
-            if let Ok(msg) = min2.get_msg() {
+            let bytes: Vec<u8> = min2.hw_if.receiver.try_iter().collect();
+            for msg in min2.process(&bytes) {
                 print!("app2 receive data: [ ");
                 for i in 0..msg.len {
                     print!("0x{:02x} ", msg.buf[i as usize]);
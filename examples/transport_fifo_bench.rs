@@ -0,0 +1,54 @@
+extern crate min_rs as min;
+use std::time::Instant;
+
+/// Measures the work of the transport FIFO per frame: queueing it, and queueing then sending it,
+/// with the FIFO emptied by a reset after each round. Nothing goes on a wire, so the encoding of
+/// the frames is the only other cost.
+/// Run with `cargo run --release --example transport_fifo_bench`.
+struct NullUart;
+
+impl min::Interface for NullUart {
+    fn tx_space(&self) -> u16 {
+        u16::MAX
+    }
+
+    fn tx_start(&self) {}
+
+    fn tx_finished(&self) {}
+
+    fn tx_byte(&self, _port: u8, _byte: u8) {}
+}
+
+const ROUNDS: u32 = 100_000;
+// The largest window: a new frame is sent by each tick
+const FRAMES_PER_ROUND: u32 = 16;
+
+fn main() {
+    let uart = NullUart;
+    let mut min = min::Context::new(
+        String::from("min"),
+        &uart,
+        0,
+        true,
+    );
+    let payload = [0x5a_u8; 255];
+    for send in [false, true] {
+        for payload_len in [1_u8, 16, 255] {
+            let start = Instant::now();
+            for _ in 0..ROUNDS {
+                for _ in 0..FRAMES_PER_ROUND {
+                    min.queue_frame(1, &payload, payload_len).ok();
+                }
+                if send {
+                    for _ in 0..FRAMES_PER_ROUND {
+                        min.tick();
+                    }
+                }
+                min.reset_transport(false).ok();
+            }
+            let elapsed = start.elapsed();
+            println!("{:<15} payload {:>3} bytes: {:>7.1} ns per frame", if send { "queue and send" } else { "queue" },
+                payload_len, elapsed.as_nanos() as f64 / (ROUNDS * FRAMES_PER_ROUND) as f64);
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::cell::Cell;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of time for the timeouts of MIN.
+pub trait Clock {
+    /// Returns the current time in milliseconds.
+    fn now_ms(&self) -> u128;
+}
+
+/// `Clock` based on the system time, used by default.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u128 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(std::time::Duration::from_secs(0)).as_millis()
+    }
+}
+
+pub(crate) static SYSTEM_CLOCK: SystemClock = SystemClock;
+
+/// `Clock` driven by hand, for tests.
+pub struct MockClock {
+    now: Cell<u128>,
+}
+
+impl MockClock {
+    pub fn new(now_ms: u128) -> Self {
+        MockClock {
+            now: Cell::new(now_ms),
+        }
+    }
+
+    pub fn set(&self, now_ms: u128) {
+        self.now.set(now_ms);
+    }
+
+    pub fn advance(&self, ms: u128) {
+        self.now.set(self.now.get().wrapping_add(ms));
+    }
+}
+
+impl Clock for MockClock {
+    fn now_ms(&self) -> u128 {
+        self.now.get()
+    }
+}
@@ -1,28 +1,104 @@
 extern crate log;
-use crate::crc::Crc32Context;
+use crate::clock::{Clock, SYSTEM_CLOCK};
+use crate::crc::{crc8_step, Crc32Context};
+use crate::rng::{Rng, XorShift32};
 use crate::transport::*;
-use std::time::{SystemTime, UNIX_EPOCH};
 use log::{warn, debug, trace};
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::collections::vec_deque::{self, Drain};
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
 
 const CRC_SEED: u32 = 0xffffffff;
 const CRC_REVERSED: bool = true;
 const CRC_REFIN: bool = false;
 const CRC_REFOUT: bool = false;
+const CRC_FINAL_XOR: u32 = 0xffffffff;
+
+/// Filter deciding whether a received frame is accepted, see `Context::set_accept_filter`
+type AcceptFilter<'a> = Box<dyn FnMut(u8, u8, &[u8]) -> bool + 'a>;
+/// Callback invoked for each received message, see `Context::set_on_rx_frame`
+type RxCallback<'a> = Box<dyn FnMut(&Msg) -> Reaction + 'a>;
+/// Handler taking the messages of an ID, see `Context::on_id`
+type IdHandler<'a> = Box<dyn FnMut(&Msg) + 'a>;
 
 /// Error
 pub enum Error {
     /// There is no enough space in tx buffer. The value is the size of bytes overflowed.
     NoEnoughTxSpace(u16),
     NoMsg,
+    /// The payload is longer than a frame can carry. The value is the length of the payload.
+    PayloadTooLong(usize),
+    /// The frame doesn't fit in the tx buffer right now, try again later.
+    WouldBlock,
+    /// The frame is larger than the tx buffer when empty, it can never be sent. The value is the
+    /// size of the frame on the wire.
+    FrameTooLarge(u16),
+}
+
+/// Error of the transport protocol operations
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TransportError {
+    /// The context doesn't use the transport protocol.
+    NotSupported,
+    /// The transport FIFO doesn't have room for all the frames. The value is the number of frames
+    /// that would have fit.
+    FifoFull(usize),
+    /// A payload is longer than a frame can carry. The value is the length of the payload.
+    PayloadTooLong(usize),
+    /// The frame is larger than what the interface reports as tx space, it would never be sent.
+    /// The value is the size of the frame on the wire.
+    FrameTooLargeForTxBuffer(u16),
+    /// The frames were not all acknowledged in time. The value is the number of frames left.
+    Timeout(usize),
+}
+
+/// Why a received frame was dropped, see `Context::drain_errors`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FrameError {
+    /// Two header bytes followed by a byte which is neither a header nor a stuff byte.
+    Stuffing {
+        byte: u8,
+    },
+    /// The length is longer than any frame that can be received.
+    Length {
+        claimed: u8,
+    },
+    /// The checksum received doesn't match the one computed over the frame.
+    Crc {
+        expected: u32,
+        actual: u32,
+    },
+    /// The header checksum received doesn't match the one computed over the header, see
+    /// `Context::set_header_crc`.
+    HeaderCrc {
+        expected: u8,
+        actual: u8,
+    },
+    /// The checksum passed but the frame doesn't end with the EOF byte.
+    Eof {
+        byte: u8,
+    },
+    /// A transport frame other than the next one expected (a duplicate or a frame after a loss).
+    SequenceMismatch {
+        seq: u8,
+        rn: u8,
+    },
 }
 
+/// Number of errors kept by `Context::drain_errors`, the oldest ones are dropped first
+pub const MAX_FRAME_ERRORS: usize = 32;
+
 /// Receiving state machine
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum RxState {
     SearchingForSof,
     ReceivingIdControl,
     ReceivingSeq,
+    ReceivingAck,
     ReceivingLength,
+    ReceivingHeaderCrc,
     ReceivingPayload,
     ReceivingChecksum3,
     ReceivingChecksum2,
@@ -39,41 +115,268 @@ const EOF_BYTE: u8 = 0x55;
 const MAX_PAYLOAD: u8 = u8::MAX;
 const MAX_MSG: u8 = 128;
 
+/// Spare bit of the ID/control byte, free for application use (e.g. a priority flag).
+/// The ID/control byte is made of the 6-bit ID (`0x3f`), this bit (`0x40`) and the transport
+/// bit (`0x80`), which is set by the transport protocol only.
+pub const SPARE_CONTROL_BIT: u8 = 0x40;
+
+/// Size in bytes reported in a `u16` error value, `u16::MAX` if larger
+fn saturate_u16(size: u32) -> u16 {
+    size.min(u16::MAX as u32) as u16
+}
+
+/// MIN ID of the frames sent by `Context::ping`
+pub const PING_ID: u8 = 0x3f;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Msg {
     pub min_id: u8,
+    /// Raw ID/control byte as received, including the spare and transport bits
+    pub control_byte: u8,
     pub len: u8,
     pub buf: Vec<u8>,
     pub port: u8,
+    /// Arrived as a transport frame (transport bit set) rather than as an application frame
+    pub is_transport: bool,
+    /// CRC32 of the frame, as received and checked
+    pub crc: u32,
 }
 
 impl Msg {
-    fn new(min_id: u8, payload: &[u8], payload_len: u8, port: u8) ->Self {
+    fn new(control_byte: u8, payload: &[u8], payload_len: u8, port: u8, is_transport: bool, crc: u32) ->Self {
         let mut buf: Vec<u8> = Vec::new();
         for i in 0..payload_len {
             buf.push(payload[i as usize]);
         }
         Msg {
-            min_id: min_id,
+            min_id: control_byte & 0x3f,
+            control_byte,
             len: payload_len,
-            buf: buf,
-            port: port,
+            buf,
+            port,
+            is_transport,
+            crc,
+        }
+    }
+}
+/// Action requested by a frame callback, applied by the context once the callback returned.
+pub enum Reaction {
+    /// Nothing to do
+    None,
+    /// Send a frame back: queued with the transport protocol, sent immediately otherwise.
+    /// A reply longer than `MAX_PAYLOAD` or which can't be queued or sent is dropped and
+    /// counted, see `get_dropped_reply_cnt`.
+    QueueReply(u8, Vec<u8>),
+}
+
+/// Which message is dropped when a message arrives while the queue is full, see
+/// `Context::set_msg_queue_capacity`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OverflowPolicy {
+    /// The message just received is dropped
+    DropNewest,
+    /// The oldest message of the queue is dropped to make room
+    DropOldest,
+}
+
+/// Snapshot of the state of the link, see `Context::health_check`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LinkHealth {
+    /// Physical link state reported by the interface
+    pub link_up: bool,
+    /// Number of transport frames sent and not yet acknowledged
+    pub in_flight: u8,
+    /// Number of frames in the transport FIFO (including the ones in flight)
+    pub queued_frames: u8,
+    /// Number of received messages not yet taken by `get_msg`
+    pub pending_msgs: usize,
+    /// Time since the last valid frame was received, `None` if nothing was received yet
+    pub ms_since_last_rx: Option<u128>,
+    /// Ratio of frames failing the checksum among the last frames received (up to 32)
+    pub crc_error_rate: f32,
+    /// There are frames in flight but nothing was heard from the other side for the idle timeout
+    pub tx_stalled: bool,
+}
+
+/// Receive errors of the last `ERROR_HISTOGRAM_WINDOW_MS`, see `Context::recent_error_histogram`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ErrorHistogram {
+    /// Frames failing the checksum
+    pub crc_errors: u32,
+    /// Frames longer than the receive buffer
+    pub length_errors: u32,
+    /// Transport frames dropped because of an unexpected sequence number
+    pub sequence_mismatches: u32,
+    /// Frames passing the checksum with a wrong EOF byte
+    pub eof_errors: u32,
+}
+
+/// Time covered by `Context::recent_error_histogram`.
+pub const ERROR_HISTOGRAM_WINDOW_MS: u128 = ERROR_HISTOGRAM_BUCKET_MS * ERROR_HISTOGRAM_BUCKETS as u128;
+const ERROR_HISTOGRAM_BUCKET_MS: u128 = 10_000;
+const ERROR_HISTOGRAM_BUCKETS: usize = 6;
+
+/// Kind of a receive error, counted by `ErrorHistogram`
+enum RxError {
+    Crc,
+    Length,
+    SequenceMismatch,
+    Eof,
+}
+
+/// What `Context::tick` sent.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct TickReport {
+    /// Number of standalone ACKs sent
+    pub acks_sent: u8,
+    /// Number of transport frames sent for the first time
+    pub frames_sent: u8,
+    /// Number of transport frames sent again because they were not acknowledged in time
+    pub frames_retransmitted: u8,
+}
+
+/// Identifies a ping sent by `Context::ping`, to get its round-trip time with `Context::take_rtt`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PingToken(u32);
+
+/// Framing options of the frames sent, copied out of the context so that a frame can be
+/// encoded while the context is borrowed mutably to transmit it.
+#[derive(Copy, Clone)]
+struct WireFormat {
+    header_len: u8,
+    datagram_mode: bool,
+    transparent_framing: bool,
+    header_crc: bool,
+    crc_covers_eof: bool,
+    crc_final_xor: u32,
+    piggyback_acks: bool,
+    /// Rn piggybacked on the transport frames
+    rn: u8,
+}
+
+impl WireFormat {
+    /// Whether a frame with the given ID/control carries a piggybacked Rn
+    fn carries_ack(&self, id_control: u8) -> bool {
+        self.piggyback_acks && (id_control & 0x80 == 0x80) && id_control != ACK && id_control != RESET
+    }
+
+    /// Checksum of the header of a frame sent: ID/control, sequence number, Rn and length
+    fn header_checksum(&self, id_control: u8, seq: u8, payload_len: u8) -> u8 {
+        let mut crc = crc8_step(0, id_control);
+        if id_control & 0x80 == 0x80 {
+            crc = crc8_step(crc, seq);
+        }
+        if self.carries_ack(id_control) {
+            crc = crc8_step(crc, self.rn);
+        }
+        crc8_step(crc, payload_len)
+    }
+
+    /// Calls `f` with every byte of a frame in the order it goes on the wire: header, stuffed
+    /// fields, checksum and EOF. The send path, `encode_into` and the size computations all
+    /// encode through it, so that they can't disagree on the framing.
+    fn for_each_wire_byte<P, F>(&self, id_control: u8, seq: u8, payload_len: u8, payload: P, mut f: F)
+    where
+        P: Iterator<Item = u8>,
+        F: FnMut(u8),
+    {
+        let stuff_after = self.header_len - 1;
+        // A datagram is never searched for a header, nor a frame with transparent framing:
+        // nothing to stuff
+        let stuffing = !self.datagram_mode && !self.transparent_framing;
+        let mut header_byte_countdown = stuff_after;
+        let mut checksum = Crc32Context::new(CRC_SEED, CRC_REVERSED, CRC_REFIN, CRC_REFOUT, self.crc_final_xor);
+        let mut stuffed = |f: &mut F, byte: u8, crc: Option<&mut Crc32Context>| {
+            if let Some(crc) = crc {
+                crc.step(byte);
+            }
+            f(byte);
+            if !stuffing {
+                return;
+            }
+            if byte == HEADER_BYTE {
+                header_byte_countdown = header_byte_countdown.saturating_sub(1);
+                if header_byte_countdown == 0 {
+                    f(STUFF_BYTE);
+                    header_byte_countdown = stuff_after;
+                }
+            } else {
+                header_byte_countdown = stuff_after;
+            }
+        };
+
+        if !self.datagram_mode {
+            // Header is 3 bytes (or `header_len`); because unstuffed will reset receiver immediately
+            for _ in 0..self.header_len {
+                f(HEADER_BYTE);
+            }
+        }
+        stuffed(&mut f, id_control, Some(&mut checksum));
+        if id_control & 0x80 == 0x80 {
+            // Send the sequence number if it is a transport frame
+            stuffed(&mut f, seq, Some(&mut checksum));
+        }
+        if self.carries_ack(id_control) {
+            // Acknowledge the frames received so far, saves a standalone ACK
+            stuffed(&mut f, self.rn, Some(&mut checksum));
+        }
+        stuffed(&mut f, payload_len, Some(&mut checksum));
+        if self.header_crc {
+            stuffed(&mut f, self.header_checksum(id_control, seq, payload_len), Some(&mut checksum));
+        }
+        for byte in payload {
+            stuffed(&mut f, byte, Some(&mut checksum));
+        }
+
+        if self.crc_covers_eof {
+            // The EOF byte is sent after the checksum but covered by it
+            checksum.step(EOF_BYTE);
+        }
+        for byte in checksum.finalize().to_be_bytes() {
+            stuffed(&mut f, byte, None);
+        }
+
+        if !self.datagram_mode {
+            // Ensure end-of-frame doesn't contain 0xaa and confuse search for start-of-frame
+            f(EOF_BYTE);
         }
     }
 }
+
 /// context for MIN.
 pub struct Context<'a, T> where T: crate::Interface {
-    pub name: String,
+    /// Identifier of the context, used as the log target
+    name: String,
     /// Use transport protocol
-    pub t_min:  bool,
+    t_min:  bool,
     /// Hardwar interface
     pub hw_if: &'a T,
     transport: Transport,
+    /// Limits and timeouts of the transport protocol
+    transport_config: TransportConfig,
     /// Number of the port associated with the context
     port: u8,
-    /// Count out the header bytes
-    tx_header_byte_countdown: u8,
-    /// Calculated checksum for sending frame
-    tx_checksum: Crc32Context,
+    /// Port of the frame being sent
+    tx_port: u8,
+    /// tx space left during a send batch (`tick`, `receive`), `None` outside of one
+    tx_budget: Option<u32>,
+    /// tx space used instead of asking the interface, see `set_tx_space_override`
+    tx_space_override: Option<u16>,
+    /// Port the bytes being received arrived on, see `receive_on`
+    rx_port: u8,
+    /// Ports whose frames are accepted, all if empty
+    accepted_ports: Vec<u8>,
+    /// Number of frames dropped because they arrived on a port not accepted
+    port_filtered_drops: u32,
+    /// Ports the application frames sent right away go out on in turn, see `set_tx_port_rotation`
+    tx_port_rotation: Vec<u8>,
+    /// Index in `tx_port_rotation` of the port of the next application frame
+    tx_port_rotation_next: usize,
+    /// Number of header bytes starting a frame, see `set_header_len`
+    header_len: u8,
     /// Countdown of header bytes to reset state
     rx_header_bytes_seen: u8,
     /// State of receiver
@@ -86,6 +389,8 @@ pub struct Context<'a, T> where T: crate::Interface {
     rx_checksum: Crc32Context,
     /// Sequence number of frame being received
     rx_frame_seq: u8,
+    /// Rn piggybacked on the transport frame being received
+    rx_frame_ack: u8,
     /// Length of frame
     rx_frame_length: u8,
     /// Control byte
@@ -95,35 +400,366 @@ pub struct Context<'a, T> where T: crate::Interface {
     /// Checksum received over the wire
     rx_frame_checksum: u32,
     msg_queue: VecDeque<Msg>,
+    /// Largest number of messages in `msg_queue`, `None` for no bound
+    msg_queue_capacity: Option<usize>,
+    /// Message dropped when `msg_queue` is full
+    overflow_policy: OverflowPolicy,
+    /// Number of received messages dropped because `msg_queue` was full
+    msg_overflow_drops: u32,
+    /// Number of frames not queued because the transport FIFO was full
+    fifo_overflow_drops: u32,
+    /// Why the last received frames were dropped, see `drain_errors`
+    error_queue: VecDeque<FrameError>,
+    /// ID and sequence number of the transport frames acknowledged, see `drain_acked`
+    acked_queue: VecDeque<(u8, u8)>,
+    /// Token of the next ping
+    next_ping: u32,
+    /// Round-trip times of the pings acknowledged, by token, until taken
+    ping_rtts: HashMap<u32, u128>,
+    /// Longest time a frame waited for its ACK since it was last sent, `None` if no frame was
+    /// acknowledged since the last `reset_max_ack_latency`
+    max_ack_latency_ms: Option<u128>,
+    /// Source of time for the timeouts
+    clock: &'a dyn Clock,
+    /// Maximum number of application frames accepted per second, `None` for no limit
+    max_rx_frames_per_sec: Option<u32>,
+    /// Start of the current rate limiting window
+    rx_rate_window_start_ms: u128,
+    /// Number of frames accepted in the current rate limiting window
+    rx_rate_window_frames: u32,
+    /// Number of frames dropped by the rate limiter
+    rate_limited_drops: u32,
+    /// Number of received messages discarded by `clear_msgs`
+    discarded_msgs: u32,
+    /// Carry Rn on outgoing transport frames instead of standalone ACKs
+    piggyback_acks: bool,
+    /// When the last valid frame was received
+    last_rx_frame_ms: Option<u128>,
+    /// ACK delay reached when the message queue is full, `None` to never throttle the ACKs
+    ack_throttle_max_ms: Option<u128>,
+    /// An ACK is owed for the frames received, sent once at the end of the receive pass
+    ack_pending: bool,
+    /// Ask for the frames from Rn again on a checksum error, see `set_nack_on_crc_error`
+    nack_on_crc_error: bool,
+    /// A NACK is owed for a transport frame failing the checksum, sent at the end of the receive pass
+    nack_pending: bool,
+    /// Number of NACKs sent
+    nacks_sent: u32,
+    /// Maximum time a frame may take to be received, `None` for no limit
+    rx_frame_timeout_ms: Option<u128>,
+    /// Cap of the retransmit timeout doubling at each retransmission of a frame, `None` for no backoff
+    retransmit_backoff_max_ms: Option<u128>,
+    /// Largest random delay added to the retransmit timeouts
+    retransmit_jitter_ms: u128,
+    /// Source of the jitter, see `set_rng`
+    rng: Box<dyn Rng + 'a>,
+    /// Bounds of the retransmit timeout computed from the round-trip times, `None` for the
+    /// configured timeout
+    adaptive_rto_bounds_ms: Option<(u128, u128)>,
+    /// Smoothed round-trip time of the transport frames, `None` before the first ACK
+    srtt_ms: Option<u128>,
+    /// Variation of the round-trip time
+    rttvar_ms: u128,
+    /// When the start of the frame being received was seen
+    rx_frame_start_ms: u128,
+    /// Number of raw bytes of the frame being received, header included
+    rx_frame_bytes: usize,
+    /// Number of frames abandoned because they took too long to be received
+    rx_frame_timeouts: u32,
+    /// Filter deciding whether a received frame is accepted, from its ID, port and payload
+    accept_filter: Option<AcceptFilter<'a>>,
+    /// Number of frames rejected by the accept filter
+    filtered_drops: u32,
+    /// Callback invoked for each received message
+    on_rx_frame: Option<RxCallback<'a>>,
+    /// Reactions returned by the callbacks, applied at the end of `receive`
+    pending_reactions: Vec<Reaction>,
+    /// Number of replies dropped because they were too long or could not be queued or sent
+    dropped_replies: u32,
+    /// Handlers taking the messages of a given ID instead of the queue
+    id_handlers: HashMap<u8, IdHandler<'a>>,
+    /// Writers the payloads of the messages with a given ID are streamed to, see `route_to_sink`
+    sinks: HashMap<u8, Box<dyn Write + 'a>>,
+    /// Number of frames dropped because of a checksum error
+    crc_errors: u32,
+    /// Number of frames passing the checksum dropped because of a wrong EOF byte
+    eof_errors: u32,
+    /// Number of ACKs not sent for lack of tx space
+    ack_send_failures: u32,
+    /// Number of RESETs not sent for lack of tx space
+    reset_send_failures: u32,
+    /// XORed with the frame checksums, see `set_crc_final_xor`
+    crc_final_xor: u32,
+    /// The EOF byte is covered by the frame checksums, see `set_crc_covers_eof`
+    crc_covers_eof: bool,
+    /// A checksum of the header follows the length byte, see `set_header_crc`
+    header_crc: bool,
+    /// Header checksum of the frame being received
+    rx_header_crc: u8,
+    /// Number of frames dropped because of a wrong header checksum
+    header_crc_errors: u32,
+    /// Whether the frames in flight were over the window at the last `poll`, see `seq_space_pressure`
+    seq_space_warned: bool,
+    /// Payload bytes of the frames sent
+    payload_bytes_sent: u64,
+    /// Bytes sent on the wire, framing and stuff bytes included
+    wire_bytes_sent: u64,
+    /// Bytes received, whether they make valid frames or not
+    rx_bytes_total: u64,
+    /// Valid frames received, ACKs and RESETs included
+    rx_frames_total: u64,
+    /// Reject the frames which can't fit the tx buffer when queued, see `set_reject_frames_over_tx_space`
+    reject_frames_over_tx_space: bool,
+    /// One frame per datagram, without header, stuffing and EOF, see `set_datagram_mode`
+    datagram_mode: bool,
+    /// No stuffing, the header is only looked for between frames, see `set_transparent_framing`
+    transparent_framing: bool,
+    /// Never transmit anything, see `set_listen_only`
+    listen_only: bool,
+    /// When the transmission was paused, see `pause_tx`
+    tx_paused_at_ms: Option<u128>,
+    /// Number of recent frames remembered to drop duplicates, 0 to disable
+    dedup_window: usize,
+    /// ID/control byte, sequence number and CRC of the recent transport frames, the oldest first
+    recent_frames: VecDeque<(u8, u8, u32)>,
+    /// Number of frames dropped as duplicates of a recent frame
+    deduped_frames: u32,
+    /// Bytes of the frame being encoded instead of sent, see `encode_ack`
+    tx_capture: Option<Vec<u8>>,
+    /// Deliver transport frames received while the transport protocol is not used as application frames
+    accept_transport_when_disabled: bool,
+    /// Number of transport frames dropped because the transport protocol is not used
+    transport_unsupported_drops: u32,
+    /// Checksum results of the last frames received, one bit per frame, set on error
+    rx_crc_history: u32,
+    /// Last raw bytes received, the oldest first, up to twice `rx_trace_len` before trimming
+    rx_trace: Vec<u8>,
+    /// Number of raw received bytes retained in `rx_trace`, 0 to disable the trace
+    rx_trace_len: usize,
+    /// Keep the raw bytes of the last frame received, see `last_frame_raw`
+    keep_frame_raw: bool,
+    /// Include the payloads in the debug logs of the frames sent and received
+    log_payloads: bool,
+    /// Raw bytes of the frame being received
+    rx_frame_raw: Vec<u8>,
+    /// Raw bytes of the last frame received
+    last_frame_raw: Vec<u8>,
+    /// Receive errors by time bucket of `ERROR_HISTOGRAM_BUCKET_MS`, with the number of the bucket
+    /// since the clock's origin, see `recent_error_histogram`
+    error_buckets: [(u128, ErrorHistogram); ERROR_HISTOGRAM_BUCKETS],
+    /// Number of valid bits in `rx_crc_history`
+    rx_crc_history_len: u8,
 }
 
 impl<'a, T> Context<'a, T> where T: crate::Interface {
     
     fn msg_enqueue(&mut self) {
-        let msg = Msg::new(self.rx_frame_id_control & 0x3f, &self.rx_frame_payload_buf, self.rx_control, self.port);
+        if !self.accepted_ports.is_empty() && !self.accepted_ports.contains(&self.rx_port) {
+            debug!(target: self.name.as_str(), "frame id={} on port {} not accepted.", self.rx_frame_id_control & 0x3f, self.rx_port);
+            self.port_filtered_drops = self.port_filtered_drops.wrapping_add(1);
+            return;
+        }
+        if let Some(accept_filter) = self.accept_filter.as_mut() {
+            let payload = &self.rx_frame_payload_buf[0..self.rx_control as usize];
+            if !accept_filter(self.rx_frame_id_control & 0x3f, self.rx_port, payload) {
+                debug!(target: self.name.as_str(), "frame id={} rejected by the filter.", self.rx_frame_id_control & 0x3f);
+                self.filtered_drops = self.filtered_drops.wrapping_add(1);
+                return;
+            }
+        }
+        // Only the frames delivered are remembered, a filtered one must not suppress a later copy
+        if self.dedup_window > 0 && self.rx_frame_id_control & 0x80 == 0x80 {
+            let key = (self.rx_frame_id_control, self.rx_frame_seq, self.rx_frame_checksum);
+            if self.recent_frames.contains(&key) {
+                debug!(target: self.name.as_str(), "duplicate of a recent frame: id={}, seq={}", self.rx_frame_id_control & 0x3f, self.rx_frame_seq);
+                self.deduped_frames = self.deduped_frames.wrapping_add(1);
+                return;
+            }
+            if self.recent_frames.len() == self.dedup_window {
+                self.recent_frames.pop_front();
+            }
+            self.recent_frames.push_back(key);
+        }
+        if let Some(sink) = self.sinks.get_mut(&(self.rx_frame_id_control & 0x3f)) {
+            let payload = &self.rx_frame_payload_buf[0..self.rx_control as usize];
+            if let Err(e) = sink.write_all(payload) {
+                warn!(target: self.name.as_str(), "sink write error, payload of id={} lost: {}", self.rx_frame_id_control & 0x3f, e);
+            }
+            return;
+        }
+        let is_transport = self.rx_frame_id_control & 0x80 == 0x80;
+        // A transport frame accepted while the transport protocol is not used is delivered as an
+        // application frame, only `is_transport` tells
+        let control_byte = if self.t_min { self.rx_frame_id_control } else { self.rx_frame_id_control & 0x7f };
+        let msg = Msg::new(control_byte, &self.rx_frame_payload_buf, self.rx_control, self.rx_port, is_transport, self.rx_frame_checksum);
+        if let Some(on_rx_frame) = self.on_rx_frame.as_mut() {
+            let reaction = on_rx_frame(&msg);
+            self.pending_reactions.push(reaction);
+        }
+        if let Some(handler) = self.id_handlers.get_mut(&msg.min_id) {
+            handler(&msg);
+        } else {
+            self.msg_queue_push(msg);
+        }
+    }
+
+    /// Queues a message for `get_msg`, dropping one according to the overflow policy if the
+    /// queue is full.
+    fn msg_queue_push(&mut self, msg: Msg) {
+        if let Some(capacity) = self.msg_queue_capacity {
+            if self.msg_queue.len() >= capacity {
+                self.msg_overflow_drops = self.msg_overflow_drops.wrapping_add(1);
+                match self.overflow_policy {
+                    OverflowPolicy::DropNewest => {
+                        warn!(target: self.name.as_str(), "message queue full, drop message id={}.", msg.min_id);
+                        return;
+                    },
+                    OverflowPolicy::DropOldest => {
+                        if let Some(oldest) = self.msg_queue.pop_front() {
+                            warn!(target: self.name.as_str(), "message queue full, drop message id={}.", oldest.min_id);
+                        }
+                        if capacity == 0 {
+                            return;
+                        }
+                    },
+                }
+            }
+        }
         self.msg_queue.push_back(msg);
     }
 
-    /// Number of bytes needed for a frame with a given payload length, excluding stuff bytes
-    /// 3 header bytes, ID/control byte, length byte, seq byte, 4 byte CRC, EOF byte
-    fn on_wire_size(&self, payload_len: u8) -> u16 {
-        (payload_len as u16) + 11
+    /// tx space available for a frame of `size` bytes. Within a send batch the interface is
+    /// queried once, then the bytes written are taken off locally; it is queried again only
+    /// when the frame doesn't fit in what is left.
+    fn tx_space(&mut self, size: u32) -> u32 {
+        if self.tx_paused_at_ms.is_some() {
+            return 0;
+        }
+        match self.tx_budget {
+            Some(budget) if budget >= size => budget,
+            Some(_) => {
+                let space = self.hw_tx_space() as u32;
+                self.tx_budget = Some(space);
+                space
+            },
+            None => self.hw_tx_space() as u32,
+        }
+    }
+
+    /// tx space reported by the interface, or the override.
+    fn hw_tx_space(&self) -> u16 {
+        self.tx_space_override.unwrap_or_else(|| self.hw_if.tx_space())
     }
 
-    fn stuffed_tx_byte(&mut self, byte: u8) {
-        // Transmit the byte
-        self.hw_if.tx_byte(self.port, byte);
+    /// Runs `f` as a send batch, see `tx_space`. Nested batches are part of the outer one.
+    fn tx_batch<R, F>(&mut self, f: F) -> R where F: FnOnce(&mut Self) -> R {
+        if self.tx_budget.is_some() {
+            return f(self);
+        }
+        // Nothing known yet: the first frame queries the interface
+        self.tx_budget = Some(0);
+        let result = f(self);
+        self.tx_budget = None;
+        result
+    }
 
-        self.tx_checksum.step(byte);
+    /// Sends the ACK owed for the frames received in this pass, if any
+    fn flush_pending_ack(&mut self) {
+        if self.nack_pending {
+            // Acknowledges up to Rn as well
+            self.nack_pending = false;
+            self.ack_pending = false;
+            self.send_nack();
+        } else if self.ack_pending {
+            self.ack_pending = false;
+            self.send_ack();
+        }
+    }
 
-        if byte == HEADER_BYTE {
-            self.tx_header_byte_countdown -= 1;
-            if self.tx_header_byte_countdown == 0 {
-                self.hw_if.tx_byte(self.port, STUFF_BYTE);
-                self.tx_header_byte_countdown = 2;
+    fn apply_reactions(&mut self) {
+        let reactions = std::mem::take(&mut self.pending_reactions);
+        for reaction in reactions {
+            match reaction {
+                Reaction::None => {},
+                Reaction::QueueReply(id, payload) => {
+                    if payload.len() > MAX_PAYLOAD as usize {
+                        warn!(target: self.name.as_str(), "reply dropped, payload too long: {} bytes.", payload.len());
+                        self.dropped_replies = self.dropped_replies.wrapping_add(1);
+                        continue;
+                    }
+                    let len = payload.len() as u8;
+                    if self.t_min {
+                        if let Err(e) = self.queue_frame(id, &payload, len) {
+                            warn!(target: self.name.as_str(), "reply dropped, not queued: {:?}", e);
+                            self.dropped_replies = self.dropped_replies.wrapping_add(1);
+                        }
+                    } else if let Err(Error::NoEnoughTxSpace(oversize)) = self.send_frame(id, &payload, len) {
+                        warn!(target: self.name.as_str(), "reply dropped, no enough tx space: oversize={}", oversize);
+                        self.dropped_replies = self.dropped_replies.wrapping_add(1);
+                    }
+                },
             }
+        }
+    }
+
+    /// Number of bytes needed for a frame with a given payload length, excluding stuff bytes
+    /// header bytes, ID/control byte, length byte, seq byte, 4 byte CRC, EOF byte
+    /// (and the piggybacked ACK byte if enabled). Counted in `u32` so that no option adding
+    /// bytes can overflow it.
+    fn on_wire_size(&self, payload_len: u8) -> u32 {
+        let size = if self.piggyback_acks {
+            (payload_len as u32) + 9 + self.header_len as u32
+        } else {
+            (payload_len as u32) + 8 + self.header_len as u32
+        };
+        if self.header_crc {
+            size + 1
         } else {
-            self.tx_header_byte_countdown = 2;
+            size
+        }
+    }
+
+    fn new_checksum(&self) -> Crc32Context {
+        Crc32Context::new(CRC_SEED, CRC_REVERSED, CRC_REFIN, CRC_REFOUT, self.crc_final_xor)
+    }
+
+    /// Framing options of the frames sent, see `WireFormat::for_each_wire_byte`
+    fn wire_format(&self) -> WireFormat {
+        WireFormat {
+            header_len: self.header_len,
+            datagram_mode: self.datagram_mode,
+            transparent_framing: self.transparent_framing,
+            header_crc: self.header_crc,
+            crc_covers_eof: self.crc_covers_eof,
+            crc_final_xor: self.crc_final_xor,
+            piggyback_acks: self.piggyback_acks,
+            rn: self.transport.rn,
+        }
+    }
+
+    /// Whether a frame with the given ID/control carries a piggybacked Rn
+    fn carries_ack(&self, id_control: u8) -> bool {
+        self.wire_format().carries_ack(id_control)
+    }
+
+    /// Number of bytes needed for a frame, including stuff bytes
+    fn on_wire_stuffed_size(&self, id_control: u8, seq: u8, payload: &[u8]) -> usize {
+        let mut size: usize = 0;
+        self.wire_format().for_each_wire_byte(id_control, seq, payload.len() as u8, payload.iter().copied(), |_| size += 1);
+        size
+    }
+
+    /// Transmits a byte, or appends it to the frame being encoded.
+    fn tx_raw_byte(&mut self, byte: u8) {
+        match self.tx_capture.as_mut() {
+            Some(buf) => buf.push(byte),
+            None => {
+                self.hw_if.tx_byte(self.tx_port, byte);
+                self.wire_bytes_sent = self.wire_bytes_sent.wrapping_add(1);
+                if let Some(budget) = self.tx_budget.as_mut() {
+                    *budget = budget.saturating_sub(1);
+                }
+            },
         }
     }
 
@@ -137,105 +773,169 @@ impl<'a, T> Context<'a, T> where T: crate::Interface {
         payload_mask: u16,
         payload_len: u8,
     ) {
-        self.tx_header_byte_countdown = 2;
-        self.tx_checksum = Crc32Context::new(CRC_SEED, CRC_REVERSED, CRC_REFIN, CRC_REFOUT);
-
-        self.hw_if.tx_start();
-
-        // Header is 3 bytes; because unstuffed will reset receiver immediately
-        self.hw_if.tx_byte(self.port, HEADER_BYTE);
-        self.hw_if.tx_byte(self.port, HEADER_BYTE);
-        self.hw_if.tx_byte(self.port, HEADER_BYTE);
-
-        self.stuffed_tx_byte(id_control);
-        if id_control & 0x80 == 0x80 {
-            // Send the sequence number if it is a transport frame
-            self.stuffed_tx_byte(seq);
+        if self.listen_only && self.tx_capture.is_none() {
+            debug!(target: self.name.as_str(), "listen only, frame id={} not sent.", id_control & 0x3f);
+            return;
         }
-
-        self.stuffed_tx_byte(payload_len);
-        let mut offset: u16 = payload_offset;
-        for _ in 0..payload_len {
-            self.stuffed_tx_byte(payload_base[offset as usize]);
-            offset += 1;
-            offset &= payload_mask;
+        if self.tx_capture.is_none() {
+            self.hw_if.tx_start();
+            self.payload_bytes_sent = self.payload_bytes_sent.wrapping_add(payload_len as u64);
+            if id_control & 0x80 == 0 {
+                debug!(target: self.name.as_str(), "send frame: id={}, len={}{}", id_control & 0x3f, payload_len,
+                    self.payload_dump((0..payload_len as u16).map(|i| payload_base[(payload_offset.wrapping_add(i) & payload_mask) as usize])));
+            }
         }
 
-        // send crc checksum
-        let checksum = self.tx_checksum.finalize();
-        self.stuffed_tx_byte((checksum >> 24) as u8 & 0xff);
-        self.stuffed_tx_byte((checksum >> 16) as u8 & 0xff);
-        self.stuffed_tx_byte((checksum >> 8) as u8 & 0xff);
-        self.stuffed_tx_byte(checksum as u8 & 0xff);
-
-        // Ensure end-of-frame doesn't contain 0xaa and confuse search for start-of-frame
-        self.hw_if.tx_byte(self.port, EOF_BYTE);
+        let format = self.wire_format();
+        let payload = (0..payload_len as u16).map(|i| payload_base[(payload_offset.wrapping_add(i) & payload_mask) as usize]);
+        format.for_each_wire_byte(id_control, seq, payload_len, payload, |byte| self.tx_raw_byte(byte));
+        if format.carries_ack(id_control) {
+            self.transport.last_sent_ack_time_ms = self.clock.now_ms();
+        }
+        // Back to the context's port for the next frame
+        self.tx_port = self.port;
 
-        self.hw_if.tx_finished();
+        if self.tx_capture.is_none() {
+            self.hw_if.tx_finished();
+        }
     }
 
     // send transport protocol frame on wire.
     fn on_wire_t_frame(&mut self, id: u8, seq: u8, payload: &[u8], len: u8) -> Result<u8, Error> {
-        let avaliable_for_send = self.hw_if.tx_space();
+        let avaliable_for_send = self.tx_space(self.on_wire_size(len));
         if self.on_wire_size(len) <= avaliable_for_send {
             trace!(target: format!("{}", self.name).as_str(), "on_wire_t_frame: min_id={}, seq={}, payload_len={}", id, seq, len);
             self.on_wire_bytes(id | 0x80_u8, seq, payload, 0, 0xffff, len);
             Ok(len)
         } else {
-            warn!(target: format!("{}", self.name).as_str(), "no enough tx space: oversize={}", (len as u16) - avaliable_for_send);
-            Err(Error::NoEnoughTxSpace((len as u16) - avaliable_for_send))
+            warn!(target: self.name.as_str(), "no enough tx space: oversize={}", (len as u32).saturating_sub(avaliable_for_send));
+            Err(Error::NoEnoughTxSpace(saturate_u16((len as u32).saturating_sub(avaliable_for_send))))
         }
     }
 
-    fn transport_fifo_frame_send(&mut self, idx: usize, update_seq: bool) {
-        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(std::time::Duration::from_secs(0)).as_millis();
+    /// Returns whether the frame was sent, it is not if there is no enough tx space.
+    fn transport_fifo_frame_send(&mut self, idx: usize, update_seq: bool) -> bool {
+        let now = self.clock.now_ms();
         self.transport.last_received_anything_ms = now;
-        // 这个地方需要发送找到的 frame，并且修改该 frame 的最后发送时间。由于借用规则的限制，需要分两步完成。
-        if let Some(mut frame) = self.transport.frames.get_mut(idx) {
-            frame.last_sent_time_ms = now;
+        // The FIFO is taken out of the context while the frame is sent, so that its payload can be
+        // borrowed without copying the frame.
+        let mut frames = std::mem::take(&mut self.transport.frames);
+        let mut sent = false;
+        if let Some(frame) = frames.get_mut(idx) {
             if update_seq {
                 frame.seq = self.transport.sn_max;
             }
+            debug!(target: self.name.as_str(), "send T-Frame: id={}, seq={}, len={}{}", frame.min_id, frame.seq, frame.payload_len,
+                self.payload_dump(frame.payload[0..frame.payload_len as usize].iter().copied()));
+            sent = self.on_wire_t_frame(frame.min_id, frame.seq, &frame.payload[0..frame.payload_len as usize], frame.payload_len).is_ok();
+            // A frame not sent (no tx space, paused) is not counted nor timed as sent
+            if sent {
+                frame.last_sent_time_ms = now;
+                if !update_seq {
+                    frame.retransmits = frame.retransmits.saturating_add(1);
+                }
+                frame.retransmit_timeout_ms = self.retransmit_timeout_ms(frame.retransmits);
+            }
+        }
+        self.transport.frames = frames;
+        sent
+    }
+
+    /// Retransmit timeout of a frame already sent again `retransmits` times: the base timeout,
+    /// doubled at each retransmission up to the backoff cap, plus the jitter.
+    fn retransmit_timeout_ms(&mut self, retransmits: u8) -> u128 {
+        let base = self.rto_ms();
+        let timeout = match self.retransmit_backoff_max_ms {
+            Some(max_timeout) if retransmits >= 64 => max_timeout.max(base),
+            Some(max_timeout) => base.saturating_mul(1 << retransmits).min(max_timeout.max(base)),
+            None => base,
+        };
+        if self.retransmit_jitter_ms > 0 {
+            timeout + self.rng.next_u32() as u128 % (self.retransmit_jitter_ms + 1)
+        } else {
+            timeout
+        }
+    }
+
+    /// Returns true if the application frame being received exceeds `max_rx_frames_per_sec`.
+    fn rx_rate_limited(&mut self) -> bool {
+        if let Some(max_frames) = self.max_rx_frames_per_sec {
+            let now = self.clock.now_ms();
+            if now.wrapping_sub(self.rx_rate_window_start_ms) >= 1000 {
+                self.rx_rate_window_start_ms = now;
+                self.rx_rate_window_frames = 0;
+            }
+            if self.rx_rate_window_frames >= max_frames {
+                self.rate_limited_drops = self.rate_limited_drops.wrapping_add(1);
+                return true;
+            }
+            self.rx_rate_window_frames += 1;
         }
-        // 这个地方有点疑惑，为什么必须是 `&mut frame`，去掉 `&mut` 会因两次可变借用而编译失败，进一步改为 `get` 后，会因可变借用和不可变借用同时发生而编译失败
-        if let Some(&mut frame) = self.transport.frames.get_mut(idx) {
-            debug!(target: format!("{}", self.name).as_str(), "send T-Frame: id={}, seq={}, len={}", frame.min_id, frame.seq, frame.payload_len);
-            self.on_wire_t_frame(frame.min_id, frame.seq, &frame.payload[0..frame.payload_len as usize], frame.payload_len).unwrap_or(0);
+        false
+    }
+
+    /// Handles an ACK carrying `rn`, standalone or piggybacked on a transport frame.
+    fn ack_received(&mut self, rn: u8, num_nacked: u8) {
+        // If we get an ACK then we remove all the acknowledged frames with seq < rn
+        // But we need to make sure we don't accidentally ACK too many because of a stale ACK from an old session
+        if seq_in_window(rn, self.transport.sn_min, self.transport.sn_max) {
+            let num_acked = seq_distance(self.transport.sn_min, rn);
+            self.transport.sn_min = rn;
+            self.transport.synchronized = true;
+            // Now pop off all the frames up to (but not including) rn
+            // The ACK contains Rn; all frames before Rn are ACKed and can be removed from the window
+            debug!(target: self.name.as_str(), "Received ACK seq={}, num_acked={}, num_nacked={}", rn, num_acked, num_nacked);
+            for _ in 0..num_acked {
+                debug!(target: self.name.as_str(), "Pop transport fifo.");
+                if let Some(frame) = self.transport.frames.front() {
+                    self.acked_queue.push_back((frame.min_id, frame.seq));
+                    let rtt = self.clock.now_ms().saturating_sub(frame.last_sent_time_ms);
+                    self.max_ack_latency_ms = Some(self.max_ack_latency_ms.map_or(rtt, |max| max.max(rtt)));
+                    if let Some(token) = frame.ping {
+                        self.ping_rtts.insert(token, rtt);
+                    }
+                    // The ACK of a frame sent again can't be matched to one of its sends
+                    if frame.retransmits == 0 {
+                        self.rtt_sample(rtt);
+                    }
+                }
+                self.transport.pop();
+            }
+            // Now retransmit the number of frames that were requested, only those in flight
+            let num_nacked = num_nacked.min(seq_distance(self.transport.sn_min, self.transport.sn_max));
+            for i in 0..num_nacked {
+                if self.transport_fifo_frame_send(i.into(), false) {
+                    self.transport.retransmits_nack = self.transport.retransmits_nack.wrapping_add(1);
+                }
+            }
+            self.check_transport_invariants();
+        } else {
+            debug!(target: self.name.as_str(), "Received spurious ACK seq={}", rn);
+            self.transport.spurious_acks = self.transport.spurious_acks.wrapping_add(1);
         }
     }
 
     /// This runs the receiving half of the transport protocol, acknowledging frames received, discarding
     /// duplicates received, and handling RESET requests.
     fn valid_frame_received(&mut self) {
+        self.last_rx_frame_ms = Some(self.clock.now_ms());
+        self.rx_frames_total = self.rx_frames_total.wrapping_add(1);
+        // ACK and RESET are never rate limited. A dropped transport frame is not acknowledged,
+        // the other side will retransmit it.
+        let is_control = self.t_min && (self.rx_frame_id_control == ACK || self.rx_frame_id_control == RESET);
+        if !is_control && self.rx_rate_limited() {
+            warn!(target: self.name.as_str(), "rx rate limit exceeded, drop frame id={}", self.rx_frame_id_control & 0x3f);
+            return;
+        }
         if self.t_min {
-            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(std::time::Duration::from_secs(0)).as_millis();
+            let now = self.clock.now_ms();
             self.transport.last_received_anything_ms = now;
             match self.rx_frame_id_control {
                 ACK => {
-                    // If we get an ACK then we remove all the acknowledged frames with seq < rn
                     // The payload byte specifies the number of NACKed frames: how many we want retransmitted because
                     // they have gone missing.
-                    // But we need to make sure we don't accidentally ACK too many because of a stale ACK from an old session
-                    let num_acked = self.rx_frame_seq.wrapping_sub(self.transport.sn_min);
-                    let num_nacked = self.rx_frame_payload_buf[0].wrapping_sub(self.rx_frame_seq);  // 好像一直会是 0
-                    let num_in_window = self.transport.sn_max.wrapping_sub(self.transport.sn_min);
-                    if num_acked <= num_in_window {
-                        self.transport.sn_min = self.rx_frame_seq;
-                        // Now pop off all the frames up to (but not including) rn
-                        // The ACK contains Rn; all frames before Rn are ACKed and can be removed from the window
-                        debug!(target: format!("{}", self.name).as_str(), "Received ACK seq={}, num_acked={}, num_nacked={}", self.rx_frame_seq, num_acked, num_nacked);
-                        for _ in 0..num_acked {
-                            debug!(target: format!("{}", self.name).as_str(), "Pop transport fifo.");
-                            self.transport.pop();
-                        }
-                        // Now retransmit the number of frames that were requested
-                        for i in 0..num_nacked {
-                            self.transport_fifo_frame_send(i.into(), false);
-                        }
-                    } else {
-                        debug!(target: format!("{}", self.name).as_str(), "Received spurious ACK seq={}", self.rx_frame_seq);
-                        self.transport.spurious_acks = self.transport.spurious_acks.wrapping_add(1);
-                    }
+                    let num_nacked = seq_distance(self.rx_frame_seq, self.rx_frame_payload_buf[0]);  // 好像一直会是 0
+                    self.ack_received(self.rx_frame_seq, num_nacked);
                 },
                 RESET => {
                     // If we get a RESET demand then we reset the transport protocol (empty the FIFO, reset the
@@ -243,24 +943,42 @@ impl<'a, T> Context<'a, T> where T: crate::Interface {
                     // We don't send anything, we just do it. The other end can send frames to see if this end is
                     // alive (pings, etc.) or just wait to get application frames.
                     self.transport.resets_received = self.transport.resets_received.wrapping_add(1);
-                    self.transport.reset_transport_fifo();
+                    self.ack_pending = false;
+                    self.nack_pending = false;
+                    let discarded = self.transport.reset_transport_fifo(self.clock.now_ms());
+                    debug!(target: self.name.as_str(), "RESET received, {} frames discarded.", discarded);
                 },
                 _ => {
                     if self.rx_frame_id_control & 0x80 == 0x80 {
+                        if self.piggyback_acks {
+                            // The frame carries an implied ACK
+                            self.ack_received(self.rx_frame_ack, 0);
+                        }
                         // Incoming application frames
                         // Reset the activity time (an idle connection will be stalled)
                         self.transport.last_received_frame_ms = now;
                         if self.rx_frame_seq == self.transport.rn {
-                            debug!(target: format!("{}", self.name).as_str(), "Incoming T-MIN frame seq={}, id={}, payload len={}",
-                                self.rx_frame_seq, self.rx_frame_id_control & 0x3f, self.rx_control);
+                            debug!(target: self.name.as_str(), "Incoming T-MIN frame seq={}, id={}, payload len={}{}",
+                                self.rx_frame_seq, self.rx_frame_id_control & 0x3f, self.rx_control, self.rx_payload_dump());
                             // Now looking for the next one in the sequence
                             self.transport.rn = self.transport.rn.wrapping_add(1);
+                            self.transport.synchronized = true;
                             // Always send an ACK back for the frame we received
                             // ACKs are short (should be about 9 microseconds to send on the wire) and
                             // this will cut the latency down.
                             // We also periodically send an ACK in case the ACK was lost, and in any case
                             // frames are re-sent.
-                            self.send_ack();
+                            // With piggybacking the ACK is left to the new frame about to be sent, if any.
+                            // When the ACKs are throttled and messages are waiting, the ACK is left to `tick`.
+                            let window_size = seq_distance(self.transport.sn_min, self.transport.sn_max);
+                            let new_frame_pending = (window_size < self.transport_config.window_size) && (self.transport.n_frames > window_size);
+                            let ack_piggybacked = self.piggyback_acks && new_frame_pending;
+                            let ack_throttled = self.ack_throttle_max_ms.is_some() && !self.msg_queue.is_empty();
+                            // Several frames received in one pass are acknowledged by a single ACK
+                            // carrying the final Rn.
+                            if !ack_piggybacked && !ack_throttled {
+                                self.ack_pending = true;
+                            }
                             // Now ready to pass this up to the application handlers
 
                             self.msg_enqueue();
@@ -268,33 +986,70 @@ impl<'a, T> Context<'a, T> where T: crate::Interface {
                             // Discard this frame because we aren't looking for it: it's either a dupe because it was
                             // retransmitted when our ACK didn't get through in time, or else it's further on in the
                             // sequence and others got dropped.
-                            warn!(target: format!("{}", self.name).as_str(), "sequence mismatch: seq={}, rn={}", self.rx_frame_seq, self.transport.rn);
+                            if seq_lt(self.rx_frame_seq, self.transport.rn) {
+                                debug!(target: self.name.as_str(), "duplicate frame: seq={}, rn={}", self.rx_frame_seq, self.transport.rn);
+                            } else {
+                                warn!(target: self.name.as_str(), "sequence mismatch: seq={}, rn={}", self.rx_frame_seq, self.transport.rn);
+                            }
                             self.transport.sequence_mismatch_drop = self.transport.sequence_mismatch_drop.wrapping_add(1);
+                            self.record_recent_error(RxError::SequenceMismatch);
+                            self.push_frame_error(FrameError::SequenceMismatch { seq: self.rx_frame_seq, rn: self.transport.rn });
                         }
                     } else {
-                        debug!(target: format!("{}", self.name).as_str(), "Incoming MIN frame id={}, payload len={}", self.rx_frame_id_control & 0x3f, self.rx_control);
+                        debug!(target: self.name.as_str(), "Incoming MIN frame id={}, payload len={}{}", self.rx_frame_id_control & 0x3f, self.rx_control, self.rx_payload_dump());
                         // Not a transport frame
                         self.msg_enqueue();
                     }
                 },
             }
         } else {
-            debug!(target: format!("{}", self.name).as_str(), "Incoming app frame id={}, payload len={}",
-                self.rx_frame_id_control & 0x3f, self.rx_control);
-                self.msg_enqueue();
+            debug!(target: self.name.as_str(), "Incoming app frame id={}, payload len={}{}",
+                self.rx_frame_id_control & 0x3f, self.rx_control, self.rx_payload_dump());
+            // A transport frame accepted while the transport protocol is not used
+            self.msg_enqueue();
         }
     }
 
     fn rx_byte(&mut self, byte: u8) {
-        // Regardless of state, three header bytes means "start of frame" and
+        self.rx_bytes_total = self.rx_bytes_total.wrapping_add(1);
+        if self.rx_trace_len > 0 {
+            // Trim only once twice the length is reached so that the trace stays contiguous
+            if self.rx_trace.len() == self.rx_trace_len * 2 {
+                self.rx_trace.drain(..self.rx_trace_len);
+            }
+            self.rx_trace.push(byte);
+        }
+        if !matches!(self.rx_frame_state, RxState::SearchingForSof) {
+            self.rx_frame_bytes += 1;
+            if self.keep_frame_raw {
+                self.rx_frame_raw.push(byte);
+            }
+        }
+
+        if self.transparent_framing && !matches!(self.rx_frame_state, RxState::SearchingForSof) {
+            // Inside a frame every byte is data, the frame ends by its length, checksum and EOF
+            self.rx_header_bytes_seen = 0;
+            self.rx_frame_byte(byte);
+            return;
+        }
+
+        // Regardless of state, three header bytes (`header_len`) means "start of frame" and
         // should reset the frame buffer and be ready to receive frame data.
         //
-        // Two in a row in over the frame means to expect a stuff byte.
-        if self.rx_header_bytes_seen == 2 {
+        // Two in a row (one less than `header_len`) in over the frame means to expect a stuff byte.
+        if self.rx_header_bytes_seen == self.header_len - 1 {
             self.rx_header_bytes_seen = 0;
             match byte {
                 HEADER_BYTE => {
                     self.rx_frame_state = RxState::ReceivingIdControl;
+                    self.rx_frame_start_ms = self.clock.now_ms();
+                    self.rx_frame_bytes = self.header_len as usize;
+                    if self.keep_frame_raw {
+                        self.rx_frame_raw.clear();
+                        for _ in 0..self.header_len {
+                            self.rx_frame_raw.push(HEADER_BYTE);
+                        }
+                    }
                     return;
                 }
                 STUFF_BYTE => {
@@ -303,6 +1058,10 @@ impl<'a, T> Context<'a, T> where T: crate::Interface {
                 }
                 _ => {
                     // Something has gone wrong, give up on this frame and look for header again.
+                    if !matches!(self.rx_frame_state, RxState::SearchingForSof) {
+                        self.push_frame_error(FrameError::Stuffing { byte });
+                        self.hw_if.on_rx_error();
+                    }
                     self.rx_frame_state = RxState::SearchingForSof;
                 }
             }
@@ -314,19 +1073,46 @@ impl<'a, T> Context<'a, T> where T: crate::Interface {
             self.rx_header_bytes_seen = 0;
         }
 
+        self.rx_frame_byte(byte);
+    }
+
+    /// Runs the receiving state machine on a byte of a frame, once the framing is removed.
+    /// The header of the frame being received is complete (and checked), go on to the payload
+    fn rx_header_received(&mut self) {
+        if self.rx_frame_length > 0 {
+            if self.rx_frame_length <= MAX_PAYLOAD {
+                self.rx_frame_state = RxState::ReceivingPayload;
+            } else {
+                // Frame dropped because it's longer than any frame we can buffer
+                self.record_recent_error(RxError::Length);
+                self.push_frame_error(FrameError::Length { claimed: self.rx_frame_length });
+                self.hw_if.on_rx_error();
+                self.rx_frame_state = RxState::SearchingForSof;
+            }
+        } else {
+            self.rx_frame_state = RxState::ReceivingChecksum3;
+        }
+    }
+
+    fn rx_frame_byte(&mut self, byte: u8) {
         match self.rx_frame_state {
             RxState::SearchingForSof => {}
             RxState::ReceivingIdControl => {
                 self.rx_frame_id_control = byte;
                 self.rx_frame_payload_bytes = 0;
-                self.rx_checksum = Crc32Context::new(CRC_SEED, CRC_REVERSED, CRC_REFIN, CRC_REFOUT);
+                self.rx_checksum = self.new_checksum();
                 self.rx_checksum.step(byte);
+                self.rx_header_crc = crc8_step(0, byte);
                 if byte & 0x80 == 0x80 {
                     if self.t_min {
                         self.rx_frame_state = RxState::ReceivingSeq;
+                    } else if self.accept_transport_when_disabled && byte != ACK && byte != RESET {
+                        // Received as an application frame, the sequence number is ignored
+                        self.rx_frame_state = RxState::ReceivingSeq;
                     } else {
                         // If there is no transport support compiled in then all transport frames are ignored
                         warn!(target: format!("{}", self.name).as_str(), "no transport support, drop this frame.");
+                        self.transport_unsupported_drops = self.transport_unsupported_drops.wrapping_add(1);
                         self.rx_frame_state = RxState::SearchingForSof;
                     }
                 } else {
@@ -337,21 +1123,42 @@ impl<'a, T> Context<'a, T> where T: crate::Interface {
             RxState::ReceivingSeq => {
                 self.rx_frame_seq = byte;
                 self.rx_checksum.step(byte);
+                self.rx_header_crc = crc8_step(self.rx_header_crc, byte);
+                if self.carries_ack(self.rx_frame_id_control) {
+                    self.rx_frame_state = RxState::ReceivingAck;
+                } else {
+                    self.rx_frame_state = RxState::ReceivingLength;
+                }
+            }
+            RxState::ReceivingAck => {
+                self.rx_frame_ack = byte;
+                self.rx_checksum.step(byte);
+                self.rx_header_crc = crc8_step(self.rx_header_crc, byte);
                 self.rx_frame_state = RxState::ReceivingLength;
             }
             RxState::ReceivingLength => {
                 self.rx_frame_length = byte;
                 self.rx_control = byte;
                 self.rx_checksum.step(byte);
-                if self.rx_frame_length > 0 {
-                    if self.rx_frame_length <= MAX_PAYLOAD {
-                        self.rx_frame_state = RxState::ReceivingPayload;
-                    } else {
-                        // Frame dropped because it's longer than any frame we can buffer
-                        self.rx_frame_state = RxState::SearchingForSof;
-                    }
+                self.rx_header_crc = crc8_step(self.rx_header_crc, byte);
+                if self.header_crc {
+                    self.rx_frame_state = RxState::ReceivingHeaderCrc;
                 } else {
-                    self.rx_frame_state = RxState::ReceivingChecksum3;
+                    self.rx_header_received();
+                }
+            }
+            RxState::ReceivingHeaderCrc => {
+                self.rx_checksum.step(byte);
+                if byte == self.rx_header_crc {
+                    self.rx_header_received();
+                } else {
+                    // The length can't be trusted, drop the frame right away rather than at the checksum
+                    warn!(target: self.name.as_str(), "header crc error, drop this frame.");
+                    self.header_crc_errors = self.header_crc_errors.wrapping_add(1);
+                    self.record_recent_error(RxError::Crc);
+                    self.push_frame_error(FrameError::HeaderCrc { expected: self.rx_header_crc, actual: byte });
+                    self.hw_if.on_rx_error();
+                    self.rx_frame_state = RxState::SearchingForSof;
                 }
             }
             RxState::ReceivingPayload => {
@@ -377,31 +1184,103 @@ impl<'a, T> Context<'a, T> where T: crate::Interface {
             }
             RxState::ReceivingChecksum0 => {
                 self.rx_frame_checksum |= byte as u32;
+                if self.crc_covers_eof {
+                    // The EOF byte is still to come, it is checked on its own
+                    self.rx_checksum.step(EOF_BYTE);
+                }
                 let crc = self.rx_checksum.finalize();
+                self.record_crc_result(crc == self.rx_frame_checksum);
                 if crc != self.rx_frame_checksum {
                     // Frame fails the checksum and so is dropped
                     warn!(target: format!("{}", self.name).as_str(), "crc error, drop this frame.");
+                    self.crc_errors = self.crc_errors.wrapping_add(1);
+                    self.record_recent_error(RxError::Crc);
+                    self.push_frame_error(FrameError::Crc { expected: crc, actual: self.rx_frame_checksum });
+                    self.frame_raw_complete();
+                    self.hw_if.on_rx_error();
                     self.rx_frame_state = RxState::SearchingForSof;
+                    let id_control = self.rx_frame_id_control;
+                    if self.nack_on_crc_error && self.t_min && id_control & 0x80 == 0x80 && id_control != ACK && id_control != RESET {
+                        self.nack_pending = true;
+                    }
                 } else {
                     // Checksum passes, go on to check for the end-of-frame marker
                     self.rx_frame_state = RxState::ReceivingEof;
                 }
             }
             RxState::ReceivingEof => {
+                self.frame_raw_complete();
                 if byte == EOF_BYTE {
                     // Frame received OK, pass up data to handler
                     self.valid_frame_received();
+                } else {
+                    // The checksum passed but the framing is off, discard
+                    warn!(target: self.name.as_str(), "eof error, drop this frame.");
+                    self.eof_errors = self.eof_errors.wrapping_add(1);
+                    self.record_recent_error(RxError::Eof);
+                    self.push_frame_error(FrameError::Eof { byte });
+                    self.hw_if.on_rx_error();
                 }
-                // else discard
                 // Look for next frame */
                 self.rx_frame_state = RxState::SearchingForSof;
             }
         }
     }
 
+    fn frame_raw_complete(&mut self) {
+        if self.keep_frame_raw {
+            std::mem::swap(&mut self.last_frame_raw, &mut self.rx_frame_raw);
+            self.rx_frame_raw.clear();
+        }
+    }
+
+    fn record_crc_result(&mut self, ok: bool) {
+        self.rx_crc_history <<= 1;
+        if !ok {
+            self.rx_crc_history |= 1;
+        }
+        if self.rx_crc_history_len < 32 {
+            self.rx_crc_history_len += 1;
+        }
+    }
+
+    /// Port of the next application frame sent right away, see `set_tx_port_rotation`
+    fn next_app_tx_port(&mut self) -> u8 {
+        match self.tx_port_rotation.get(self.tx_port_rotation_next) {
+            Some(&port) => {
+                self.tx_port_rotation_next = (self.tx_port_rotation_next + 1) % self.tx_port_rotation.len();
+                port
+            },
+            None => self.port,
+        }
+    }
+
+    fn push_frame_error(&mut self, error: FrameError) {
+        if self.error_queue.len() == MAX_FRAME_ERRORS {
+            self.error_queue.pop_front();
+        }
+        self.error_queue.push_back(error);
+    }
+
+    fn record_recent_error(&mut self, error: RxError) {
+        let epoch = self.clock.now_ms() / ERROR_HISTOGRAM_BUCKET_MS;
+        let bucket = &mut self.error_buckets[(epoch % ERROR_HISTOGRAM_BUCKETS as u128) as usize];
+        if bucket.0 != epoch {
+            // The bucket was last used a full window ago
+            *bucket = (epoch, ErrorHistogram::default());
+        }
+        let counts = &mut bucket.1;
+        match error {
+            RxError::Crc => counts.crc_errors = counts.crc_errors.wrapping_add(1),
+            RxError::Length => counts.length_errors = counts.length_errors.wrapping_add(1),
+            RxError::SequenceMismatch => counts.sequence_mismatches = counts.sequence_mismatches.wrapping_add(1),
+            RxError::Eof => counts.eof_errors = counts.eof_errors.wrapping_add(1),
+        }
+    }
+
     fn find_retransmit_frame(&mut self) -> (usize, u128) {
-        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(std::time::Duration::from_secs(0)).as_millis();
-        let window_size = self.transport.sn_max.wrapping_sub(self.transport.sn_min);
+        let now = self.clock.now_ms();
+        let window_size = seq_distance(self.transport.sn_min, self.transport.sn_max);
         let mut oldest_elapsed_time: u128 = 0;
         let mut oldest_frame_index: usize = 0;
         let mut last_sent_time_ms = 0;
@@ -418,25 +1297,95 @@ impl<'a, T> Context<'a, T> where T: crate::Interface {
         (oldest_frame_index, last_sent_time_ms)
     }
 
-    fn push(&mut self, frame: TransportFrame) {
+    /// Appends a frame to the transport FIFO, returns false if the FIFO is full: the frame is
+    /// dropped and counted, the frames already queued are never dropped.
+    fn push(&mut self, frame: TransportFrame) -> bool {
+        if self.transport.n_frames >= self.transport_config.fifo_capacity {
+            warn!(target: self.name.as_str(), "transport FIFO full, drop frame id={}.", frame.min_id);
+            self.fifo_overflow_drops = self.fifo_overflow_drops.wrapping_add(1);
+            return false;
+        }
+        debug!(target: self.name.as_str(), "Queued ID={}, len={}", frame.min_id, frame.payload_len);
         self.transport.frames.push_back(frame);
-        self.transport.n_frames = self.transport.n_frames.wrapping_add(1);
+        #[cfg(feature = "debug-checks")]
+        {
+            self.transport.n_frames = self.transport.n_frames.checked_add(1).expect("n_frames overflow");
+        }
+        #[cfg(not(feature = "debug-checks"))]
+        {
+            self.transport.n_frames = self.transport.n_frames.wrapping_add(1);
+        }
         if self.transport.n_frames_max < self.transport.n_frames {
             self.transport.n_frames_max = self.transport.n_frames;
         }
-        debug!(target: format!("{}", self.name).as_str(), "Queued ID={}, len={}", frame.min_id, frame.payload_len);
+        self.check_transport_invariants();
+        true
+    }
+
+    /// Checks the invariants of the transport window and FIFO, with the `debug-checks`
+    /// feature only: the sequence number arithmetic wraps and would hide a broken state.
+    #[cfg(feature = "debug-checks")]
+    fn check_transport_invariants(&self) {
+        let window_size = seq_distance(self.transport.sn_min, self.transport.sn_max);
+        assert!(window_size <= self.transport_config.window_size,
+            "window size {} over {}", window_size, self.transport_config.window_size);
+        assert!(window_size <= self.transport.n_frames,
+            "window size {} over the {} frames in the FIFO", window_size, self.transport.n_frames);
+        assert_eq!(self.transport.frames.len(), self.transport.n_frames as usize,
+            "FIFO length inconsistent with n_frames");
     }
 
-    fn send_ack(&mut self) {
-        let now =SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(std::time::Duration::from_secs(0)).as_millis();
+    #[cfg(not(feature = "debug-checks"))]
+    fn check_transport_invariants(&self) {}
+
+    /// Returns whether the ACK was sent, it is not if there is no enough tx space.
+    fn send_ack(&mut self) -> bool {
+        if self.listen_only || self.tx_paused_at_ms.is_some() {
+            return false;
+        }
+        let now = self.clock.now_ms();
         debug!(target: format!("{}", self.name).as_str(), "send ACK: seq={}", self.transport.rn);
-        self.on_wire_t_frame(ACK, self.transport.rn, &[self.transport.rn][0..1], 1).unwrap_or(0);
+        let sent = self.on_wire_t_frame(ACK, self.transport.rn, &[self.transport.rn][0..1], 1).is_ok();
+        if !sent {
+            self.ack_send_failures = self.ack_send_failures.wrapping_add(1);
+        }
+        self.transport.last_sent_ack_time_ms = now;
+        sent
+    }
+
+    /// Sends an ACK for Rn asking for the frame Rn again, see `set_nack_on_crc_error`. Returns
+    /// whether it was sent, like `send_ack`.
+    fn send_nack(&mut self) -> bool {
+        if self.listen_only || self.tx_paused_at_ms.is_some() {
+            return false;
+        }
+        let now = self.clock.now_ms();
+        debug!(target: self.name.as_str(), "send NACK: seq={}", self.transport.rn);
+        // The payload is the end of the frames to send again: just Rn
+        let sent = self.on_wire_t_frame(ACK, self.transport.rn, &[self.transport.rn.wrapping_add(1)][0..1], 1).is_ok();
+        if sent {
+            self.nacks_sent = self.nacks_sent.wrapping_add(1);
+        } else {
+            self.ack_send_failures = self.ack_send_failures.wrapping_add(1);
+        }
         self.transport.last_sent_ack_time_ms = now;
+        sent
     }
 
-    fn send_reset(&mut self) {
+    /// Returns whether the RESET was sent, it is not if there is no enough tx space: nothing
+    /// is written rather than a truncated frame.
+    fn send_reset(&mut self) -> bool {
+        if self.listen_only || self.tx_paused_at_ms.is_some() {
+            return false;
+        }
+        if self.on_wire_size(0) > self.tx_space(self.on_wire_size(0)) {
+            warn!(target: self.name.as_str(), "no enough tx space to send RESET");
+            self.reset_send_failures = self.reset_send_failures.wrapping_add(1);
+            return false;
+        }
         debug!(target: format!("{}", self.name).as_str(), "send RESET");
         self.on_wire_bytes(RESET, 0, &[0][0..0], 0, 0, 0);
+        true
     }
 }
 
@@ -459,116 +1408,1262 @@ impl<'a, T> Context<'a, T> where T: crate::Interface{
     ) -> Self {
         Context {
             transport: Transport::new(),
+            transport_config: TransportConfig::default(),
             hw_if: hw_if,
             name: name,
             port: port,
+            tx_port: port,
+            rx_port: port,
+            accepted_ports: Vec::new(),
+            port_filtered_drops: 0,
+            tx_budget: None,
+            tx_space_override: None,
+            tx_port_rotation: Vec::new(),
+            tx_port_rotation_next: 0,
             t_min: t_min,
-            tx_header_byte_countdown: 2,
-            tx_checksum: Crc32Context::new(CRC_SEED, CRC_REVERSED, CRC_REFIN, CRC_REFOUT),
+            header_len: 3,
             rx_header_bytes_seen: 0,
             rx_frame_state: RxState::SearchingForSof,
             rx_frame_id_control: 0,
             rx_frame_payload_bytes: 0,
-            rx_checksum: Crc32Context::new(CRC_SEED, CRC_REVERSED, CRC_REFIN, CRC_REFOUT),
+            rx_checksum: Crc32Context::new(CRC_SEED, CRC_REVERSED, CRC_REFIN, CRC_REFOUT, CRC_FINAL_XOR),
             rx_frame_seq: 0,
+            rx_frame_ack: 0,
             rx_frame_length: 0,
             rx_control: 0,
             rx_frame_payload_buf: [0; MAX_PAYLOAD as usize],
             rx_frame_checksum: 0,
             msg_queue: VecDeque::with_capacity(MAX_MSG as usize),
+            msg_queue_capacity: None,
+            overflow_policy: OverflowPolicy::DropNewest,
+            msg_overflow_drops: 0,
+            fifo_overflow_drops: 0,
+            error_queue: VecDeque::new(),
+            acked_queue: VecDeque::new(),
+            next_ping: 0,
+            ping_rtts: HashMap::new(),
+            max_ack_latency_ms: None,
+            clock: &SYSTEM_CLOCK,
+            max_rx_frames_per_sec: None,
+            rx_rate_window_start_ms: 0,
+            rx_rate_window_frames: 0,
+            rate_limited_drops: 0,
+            discarded_msgs: 0,
+            piggyback_acks: false,
+            last_rx_frame_ms: None,
+            ack_throttle_max_ms: None,
+            ack_pending: false,
+            nack_on_crc_error: false,
+            nack_pending: false,
+            nacks_sent: 0,
+            rx_frame_timeout_ms: None,
+            rx_frame_start_ms: 0,
+            rx_frame_bytes: 0,
+            rx_frame_timeouts: 0,
+            retransmit_backoff_max_ms: None,
+            retransmit_jitter_ms: 0,
+            rng: Box::new(XorShift32::new(1)),
+            adaptive_rto_bounds_ms: None,
+            srtt_ms: None,
+            rttvar_ms: 0,
+            accept_filter: None,
+            filtered_drops: 0,
+            on_rx_frame: None,
+            pending_reactions: Vec::new(),
+            dropped_replies: 0,
+            id_handlers: HashMap::new(),
+            sinks: HashMap::new(),
+            crc_errors: 0,
+            error_buckets: [(0, ErrorHistogram::default()); ERROR_HISTOGRAM_BUCKETS],
+            payload_bytes_sent: 0,
+            wire_bytes_sent: 0,
+            rx_bytes_total: 0,
+            rx_frames_total: 0,
+            eof_errors: 0,
+            ack_send_failures: 0,
+            reset_send_failures: 0,
+            crc_final_xor: CRC_FINAL_XOR,
+            crc_covers_eof: false,
+            header_crc: false,
+            rx_header_crc: 0,
+            header_crc_errors: 0,
+            seq_space_warned: false,
+            reject_frames_over_tx_space: false,
+            datagram_mode: false,
+            transparent_framing: false,
+            listen_only: false,
+            tx_paused_at_ms: None,
+            dedup_window: 0,
+            recent_frames: VecDeque::new(),
+            deduped_frames: 0,
+            tx_capture: None,
+            accept_transport_when_disabled: false,
+            transport_unsupported_drops: 0,
+            rx_crc_history: 0,
+            rx_crc_history_len: 0,
+            rx_trace: Vec::new(),
+            rx_trace_len: 0,
+            keep_frame_raw: false,
+            log_payloads: false,
+            rx_frame_raw: Vec::new(),
+            last_frame_raw: Vec::new(),
         }
     }
 
-    /// Sends an application MIN frame on the wire (do not put into the transport queue),
-    /// returning the number of bytes sent or crate::Error.
+    /// Construct a transport `Context` resuming from a previously saved `TransportState`.
+    ///
+    /// The sequence numbers are restored, the transport FIFO starts empty: payloads of frames
+    /// that were queued or in flight when the state was taken are lost, and `state.sn_max` is
+    /// rewound to `state.sn_min`. See `TransportState` for when it is safe to resume instead of
+    /// resetting the transport.
     /// # Arguments
-    /// * `id` - Identifier/Control
-    /// * `payload` - data to send
-    /// * `len` - length of payload
-    pub fn send_frame(&mut self, id: u8, payload: &[u8], len: u8) -> Result<u8, Error> {
-        let avaliable_for_send = self.hw_if.tx_space();
-        if self.on_wire_size(len) <= avaliable_for_send {
-            self.on_wire_bytes(id & 0x3f_u8, 0, payload, 0, 0xffff, len);
-            Ok(len)
-        } else {
-            Err(Error::NoEnoughTxSpace((len as u16) - avaliable_for_send))
-        }
+    /// * `name` - identifier string for debug.
+    /// * `hw_if` - Reference of hardware interface.
+    /// * `port` - Number of the port associated with the context.
+    /// * `state` - Sequence numbers to restore.
+    pub fn from_transport_state(
+        name: String,
+        hw_if: &'a T,
+        port: u8,
+        state: TransportState,
+    ) -> Self {
+        let mut context = Context::new(name, hw_if, port, true);
+        context.transport.set_state(state);
+        context
     }
 
-    pub fn reset_transport(&mut self, inform_other_side: bool) -> Result<(), String> {
-        if self.t_min {
-            debug!(target: format!("{}", self.name).as_str(), "reset transport(clear the fifo, restart timing).");
-            if inform_other_side {
-                self.send_reset();
-            }
-            self.transport.reset_transport_fifo();
-            Ok(())
-        } else {
-            warn!(target: format!("{}", self.name).as_str(), "no transport support.");
-            Err(String::from("no transport support."))
+    /// Construct a transport `Context` taking over a link from `extract_state`, so that the
+    /// other side never sees a reset.
+    ///
+    /// The `pending` frames are queued again. The in-flight ones, at the front, are sent again
+    /// with their original sequence numbers (from `state.sn_min`, see `from_transport_state`):
+    /// the other side drops those it already received as duplicates. If `pending` is empty this
+    /// is `from_transport_state`. The frames beyond the FIFO capacity are dropped, see
+    /// `get_fifo_overflow_drop_cnt`.
+    ///
+    /// Returns `TransportError::PayloadTooLong` if a pending payload is longer than
+    /// `MAX_PAYLOAD`, nothing is restored then.
+    /// # Arguments
+    /// * `name` - identifier string for debug.
+    /// * `hw_if` - Reference of hardware interface.
+    /// * `port` - Number of the port associated with the context.
+    /// * `state` - Sequence numbers to restore.
+    /// * `pending` - ID and payload of the frames not acknowledged yet, in order.
+    pub fn resume(
+        name: String,
+        hw_if: &'a T,
+        port: u8,
+        state: TransportState,
+        pending: Vec<(u8, Vec<u8>)>,
+    ) -> Result<Self, TransportError> {
+        if let Some((_, payload)) = pending.iter().find(|(_, payload)| payload.len() > MAX_PAYLOAD as usize) {
+            return Err(TransportError::PayloadTooLong(payload.len()));
+        }
+        let mut context = Context::from_transport_state(name, hw_if, port, state);
+        for (id, payload) in pending {
+            let frame = TransportFrame::new(id, &payload, payload.len() as u8);
+            context.push(frame);
         }
+        Ok(context)
     }
 
-    /// Queues a MIN ID / payload frame into the outgoing FIFO(T-MIN only)
-    /// Returns true if the frame was queued or false if context doesn't support transport protocol
-    pub fn queue_frame(&mut self, id: u8, payload: &[u8], len: u8) -> Result<(), String> {
-        if self.t_min {
-            let frame = TransportFrame::new(id, payload, len);
-            self.push(frame);
-            Ok(())
-        } else {
+    /// Replaces the limits and timeouts of the transport protocol, after checking them with
+    /// `TransportConfig::validate`. Meant to be chained after a constructor:
+    /// `Context::new(...).with_transport_config(config)?`.
+    pub fn with_transport_config(mut self, config: TransportConfig) -> Result<Self, ConfigError> {
+        config.validate()?;
+        self.transport_config = config;
+        Ok(self)
+    }
+
+    /// Changes the timeouts of the transport protocol on a running context, e.g. to follow the
+    /// latency of the link. The new values are checked with `TransportConfig::validate` and
+    /// left unchanged on error. Frames in flight keep the retransmit timeout they were sent with
+    /// until they are sent again.
+    pub fn set_timeouts(&mut self, ack_ms: u128, retransmit_ms: u128, idle_ms: u128) -> Result<(), ConfigError> {
+        let config = TransportConfig {
+            ack_timeout_ms: ack_ms,
+            retransmit_timeout_ms: retransmit_ms,
+            idle_timeout_ms: idle_ms,
+            ..self.transport_config
+        };
+        config.validate()?;
+        self.transport_config = config;
+        Ok(())
+    }
+
+    /// Returns the limits and timeouts of the transport protocol.
+    pub fn transport_config(&self) -> TransportConfig {
+        self.transport_config
+    }
+
+    /// Returns a one-line summary of the configuration of the context, e.g. to log at startup or
+    /// paste in a bug report.
+    pub fn describe(&self) -> String {
+        let mut description = format!("name={}, port={}, transport={}", self.name, self.port, if self.t_min { "on" } else { "off" });
+        if self.t_min {
+            let config = &self.transport_config;
+            description.push_str(&format!(", window_size={}, fifo_capacity={}, ack_timeout_ms={}, retransmit_timeout_ms={}, idle_timeout_ms={}, piggyback_acks={}",
+                config.window_size, config.fifo_capacity, config.ack_timeout_ms, config.retransmit_timeout_ms, config.idle_timeout_ms, self.piggyback_acks));
+        }
+        description.push_str(&format!(", max_payload={}, checksum=CRC-32 (final xor 0x{:08x}{}){}",
+            MAX_PAYLOAD, self.crc_final_xor, if self.crc_covers_eof { ", covers EOF" } else { "" },
+            if self.header_crc { " + header CRC-8" } else { "" }));
+        let framing = if self.datagram_mode {
+            String::from("datagram")
+        } else {
+            let header: Vec<String> = (0..self.header_len).map(|_| format!("0x{:02x}", HEADER_BYTE)).collect();
+            format!("[header {} / stuff 0x{:02x}{} / EOF 0x{:02x}]", header.join(" "),
+                STUFF_BYTE, if self.transparent_framing { " (transparent, unused)" } else { "" }, EOF_BYTE)
+        };
+        description.push_str(&format!(", framing={}", framing));
+        description
+    }
+
+    /// Consumes the context, returning its transport state and the ID and payload of the
+    /// frames not acknowledged yet, in order: the in-flight frames (sent, waiting for an ACK)
+    /// first, then the frames not sent yet. See `resume`.
+    ///
+    /// Received messages not taken yet are lost.
+    pub fn extract_state(mut self) -> (TransportState, Vec<(u8, Vec<u8>)>) {
+        let pending = self.transport.frames
+            .drain(..)
+            .map(|frame| (frame.min_id, frame.payload[0..frame.payload_len as usize].to_vec()))
+            .collect();
+        (self.transport.get_state(), pending)
+    }
+
+    /// Returns the identifier of the context, used as the log target.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Renames the context (e.g. once the identity of the other side is known), the following
+    /// logs use the new name as target.
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    /// Returns whether the transport protocol is used.
+    pub fn transport_enabled(&self) -> bool {
+        self.t_min
+    }
+
+    /// Returns the largest payload a frame can carry, in bytes.
+    pub fn max_payload(&self) -> usize {
+        MAX_PAYLOAD as usize
+    }
+
+    /// Returns the largest number of transport frames in flight (sent and not acknowledged yet).
+    pub fn max_window(&self) -> usize {
+        self.transport_config.window_size as usize
+    }
+
+    /// Returns the largest number of frames in the transport FIFO, in flight ones included.
+    pub fn fifo_capacity(&self) -> usize {
+        self.transport_config.fifo_capacity as usize
+    }
+
+    /// Starts using the transport protocol, with an empty FIFO and the sequence numbers reset.
+    pub fn enable_transport(&mut self) {
+        if !self.t_min {
+            self.transport.reset_transport_fifo(self.clock.now_ms());
+            self.t_min = true;
+        }
+    }
+
+    /// Stops using the transport protocol, the frames in the FIFO are discarded.
+    pub fn disable_transport(&mut self) {
+        if self.t_min {
+            self.transport.reset_transport_fifo(self.clock.now_ms());
+            self.t_min = false;
+        }
+    }
+
+    /// Only accepts the frames received on `ports` (see `receive_on`), the others are dropped and
+    /// counted before the filter and the callbacks. An empty list (default) accepts all the
+    /// ports.
+    ///
+    /// Like with `set_accept_filter`, a transport frame on a port not accepted is still
+    /// acknowledged: it was received correctly, the other side must not retransmit it.
+    pub fn set_accepted_ports(&mut self, ports: &[u8]) {
+        self.accepted_ports = ports.to_vec();
+    }
+
+    /// Registers a filter consulted for each valid frame received, with its ID, port and payload:
+    /// the frame is dropped and counted if it returns false, it is not passed to the callbacks
+    /// nor queued.
+    ///
+    /// A filtered transport frame is still acknowledged: it was received correctly, the other
+    /// side must not retransmit it.
+    pub fn set_accept_filter<F>(&mut self, accept_filter: F) where F: FnMut(u8, u8, &[u8]) -> bool + 'a {
+        self.accept_filter = Some(Box::new(accept_filter));
+    }
+
+    /// Registers a callback invoked for each received message, before it is queued for `get_msg`.
+    ///
+    /// The callback runs while the context is borrowed, so it can't call into the context: it
+    /// returns a `Reaction` instead, which is applied once all the received bytes are processed.
+    pub fn set_on_rx_frame<F>(&mut self, on_rx_frame: F) where F: FnMut(&Msg) -> Reaction + 'a {
+        self.on_rx_frame = Some(Box::new(on_rx_frame));
+    }
+
+    /// Registers a handler for the messages with the given ID: they are passed to the handler
+    /// instead of being queued for `get_msg`. Messages with other IDs are queued as usual.
+    ///
+    /// Handlers are called while the frames are decoded, in arrival order, and replace any
+    /// handler previously registered for the same ID.
+    pub fn on_id<F>(&mut self, id: u8, handler: F) where F: FnMut(&Msg) + 'a {
+        self.id_handlers.insert(id & 0x3f, Box::new(handler));
+    }
+
+    /// Streams the payloads of the messages with the given ID to `sink` (a file, a socket...) as
+    /// they arrive, instead of queueing a `Msg` for each. Replaces any sink previously routed for
+    /// the same ID.
+    ///
+    /// With the transport protocol the payloads are written in order without gaps: frames out of
+    /// sequence are dropped and sent again by the other side. Without it, lost frames leave gaps.
+    pub fn route_to_sink<W>(&mut self, id: u8, sink: W) where W: Write + 'a {
+        self.sinks.insert(id & 0x3f, Box::new(sink));
+    }
+
+    /// Carries the current Rn on every outgoing transport frame (one extra byte after the sequence
+    /// number), so that no standalone ACK is needed when there is data to send.
+    ///
+    /// This changes the wire format of transport frames: both sides must enable it, it is not
+    /// compatible with other MIN implementations.
+    pub fn set_piggyback_acks(&mut self, enable: bool) {
+        self.piggyback_acks = enable;
+    }
+
+    /// When the transport protocol is not used, delivers the transport frames received as
+    /// application frames (the transport bit is stripped) instead of dropping them.
+    /// ACK and RESET frames are always dropped. Disabled by default.
+    pub fn set_accept_transport_when_disabled(&mut self, accept: bool) {
+        self.accept_transport_when_disabled = accept;
+    }
+
+    /// Bounds the queue of received messages to `capacity` messages, `None` (default) for no
+    /// bound. When a message arrives while the queue is full, one is dropped according to
+    /// `policy` and counted, see `get_msg_overflow_drop_cnt`.
+    pub fn set_msg_queue_capacity(&mut self, capacity: Option<usize>, policy: OverflowPolicy) {
+        self.msg_queue_capacity = capacity;
+        self.overflow_policy = policy;
+        if let Some(capacity) = capacity {
+            while self.msg_queue.len() > capacity {
+                self.msg_queue.pop_front();
+                self.msg_overflow_drops = self.msg_overflow_drops.wrapping_add(1);
+            }
+        }
+    }
+
+    /// Delays the ACKs progressively as the received messages pile up, so that the other side
+    /// slows down: the ACK delay grows from the normal ACK retransmit timeout to `max_delay_ms`
+    /// as the message queue fills, up to its capacity (see `set_msg_queue_capacity`) or `MAX_MSG`
+    /// messages if unbounded. `None` (default) disables the throttling.
+    pub fn set_ack_throttle(&mut self, max_delay_ms: Option<u128>) {
+        self.ack_throttle_max_ms = max_delay_ms;
+    }
+
+    /// When a transport frame fails the checksum, answers right away with an ACK for the current
+    /// Rn asking for the frame Rn again (a NACK), rather than waiting for the other side's
+    /// retransmit timeout. Recovers much faster from corrupted frames, at the cost of an extra
+    /// ACK per checksum error: on a noisy link, or when the corrupted frame was not the one
+    /// awaited, the frames sent again only use up bandwidth. Disabled by default.
+    pub fn set_nack_on_crc_error(&mut self, enable: bool) {
+        self.nack_on_crc_error = enable;
+    }
+
+    /// Sets the value XORed with the checksum of the frames sent and received. The default
+    /// `0xffffffff` inverts the CRC as MIN does, `0` matches a peer which doesn't invert it.
+    pub fn set_crc_final_xor(&mut self, final_xor: u32) {
+        self.crc_final_xor = final_xor;
+    }
+
+    /// Covers the EOF byte by the checksum of the frames sent and received, for a stricter
+    /// variant of MIN. The EOF byte is still sent after the checksum. Both sides must enable
+    /// it, disabled by default.
+    pub fn set_crc_covers_eof(&mut self, covers_eof: bool) {
+        self.crc_covers_eof = covers_eof;
+    }
+
+    /// Adds a checksum of the header (ID/control, sequence number, Rn and length) after the length
+    /// byte of the frames sent, and checks it on the frames received: a frame with a corrupted
+    /// length is dropped before its payload is read. This changes the wire format, both sides
+    /// must enable it; disabled by default.
+    pub fn set_header_crc(&mut self, header_crc: bool) {
+        self.header_crc = header_crc;
+    }
+
+    /// Replaces the source of time used for the timeouts (the system time by default).
+    /// The transport timers are restarted from the new clock.
+    pub fn set_clock(&mut self, clock: &'a dyn Clock) {
+        self.clock = clock;
+        self.transport.restart_timers(clock.now_ms());
+        self.rx_rate_window_start_ms = clock.now_ms();
+    }
+
+    /// Abandons a frame being received if it is not complete `timeout_ms` after its start,
+    /// checked in `tick`. `None` (default) disables the timeout.
+    pub fn set_rx_frame_timeout(&mut self, timeout_ms: Option<u128>) {
+        self.rx_frame_timeout_ms = timeout_ms;
+    }
+
+    /// Sends the application frames sent right away (`send_frame`, `send`...) on each port of
+    /// `ports` in turn, e.g. for a gateway spreading frames over several links. An empty list
+    /// (default) sends them all on the context's port.
+    ///
+    /// The transport frames, ACKs and RESETs always go out on the context's port: the sequence
+    /// numbers are those of a single link with a single peer.
+    pub fn set_tx_port_rotation(&mut self, ports: &[u8]) {
+        self.tx_port_rotation = ports.to_vec();
+        self.tx_port_rotation_next = 0;
+    }
+
+    /// Uses `space` as the tx space available for each frame instead of asking the interface,
+    /// e.g. for an OS-buffered link that always takes a whole frame. `None` (default) asks
+    /// `Interface::tx_space`.
+    pub fn set_tx_space_override(&mut self, space: Option<u16>) {
+        self.tx_space_override = space;
+    }
+
+    /// Rejects in `queue_frame` the frames larger than the tx space reported by the interface
+    /// at that time, with `TransportError::FrameTooLargeForTxBuffer`: such a frame would stay
+    /// stuck in the FIFO. Only meaningful if `tx_space` reports the size of the tx buffer when
+    /// nothing is being sent, so that a lack of space is permanent. Disabled by default.
+    pub fn set_reject_frames_over_tx_space(&mut self, reject: bool) {
+        self.reject_frames_over_tx_space = reject;
+    }
+
+    /// Frames each datagram of a packet-based link (UDP, CAN...) instead of a byte stream: a
+    /// frame is sent as its ID/control byte, sequence number, length, payload and checksum,
+    /// without the header, the stuff bytes and the EOF. The bytes written between `tx_start`
+    /// and `tx_finished` make one datagram, and the datagrams received are passed to
+    /// `receive_datagram`. Both sides must agree. Disabled by default.
+    pub fn set_datagram_mode(&mut self, enable: bool) {
+        self.datagram_mode = enable;
+    }
+
+    /// Sends the frames without stuff bytes, so that a payload may hold three `0xaa` in a row, and
+    /// looks for the header only between frames: once a frame has started, its length, checksum and
+    /// EOF alone tell where it ends. This trades robustness for transparency: after a corrupted
+    /// length the receiver only resyncs once the frame fails, possibly swallowing the next frame.
+    /// Both sides must enable it, disabled by default.
+    pub fn set_transparent_framing(&mut self, enable: bool) {
+        self.transparent_framing = enable;
+    }
+
+    /// Starts the frames with `len` header bytes instead of 3, saving a byte per frame on slow
+    /// links: with 2, a stuff byte follows every `0xaa` of the frame instead of every second one
+    /// in a row. A frame boundary is then found from fewer bytes, so noise or a corrupted
+    /// stuff byte more easily passes for the start of a frame, and resyncing after an error is
+    /// less reliable: only for controlled environments. Both sides must use the same length.
+    ///
+    /// Panics if `len` is not 2 or 3: a single header byte can't be told apart from data.
+    pub fn set_header_len(&mut self, len: u8) {
+        assert!(len == 2 || len == 3, "header length {} not 2 or 3", len);
+        self.header_len = len;
+        self.rx_header_bytes_seen = 0;
+    }
+
+    /// Makes the context a passive listener, e.g. a sniffer that must not disturb the bus: the
+    /// received frames are decoded, counted and queued as usual, but nothing is ever written to
+    /// the interface. No ACK, RESET or retransmission is sent, and the frames the application
+    /// sends are silently dropped (`encode_frame` and the like still work). Disabled by default.
+    ///
+    /// With the transport protocol, the listener follows the sequence numbers from 0: when it
+    /// starts in the middle of a session, or misses a frame, the following frames are dropped
+    /// as sequence mismatches until the other sides reset.
+    pub fn set_listen_only(&mut self, enable: bool) {
+        self.listen_only = enable;
+    }
+
+    /// Stops transmitting until `resume_tx`, e.g. while another device has the bus. Frames can
+    /// still be queued, they are sent once resumed; the application frames sent meanwhile fail
+    /// with `Error::NoEnoughTxSpace`, and the ACKs and RESETs due are not sent. Received frames
+    /// are handled as usual.
+    pub fn pause_tx(&mut self) {
+        if self.tx_paused_at_ms.is_none() {
+            debug!(target: self.name.as_str(), "tx paused.");
+            self.tx_paused_at_ms = Some(self.clock.now_ms());
+        }
+    }
+
+    /// Resumes transmitting after `pause_tx`. The retransmit timers of the frames in flight are
+    /// pushed back by the time spent paused, so that they don't all fall due at once.
+    pub fn resume_tx(&mut self) {
+        if let Some(paused_at_ms) = self.tx_paused_at_ms.take() {
+            let paused_ms = self.clock.now_ms().wrapping_sub(paused_at_ms);
+            debug!(target: self.name.as_str(), "tx resumed after {} ms.", paused_ms);
+            let in_flight = seq_distance(self.transport.sn_min, self.transport.sn_max) as usize;
+            for frame in self.transport.frames.iter_mut().take(in_flight) {
+                frame.last_sent_time_ms = frame.last_sent_time_ms.wrapping_add(paused_ms);
+            }
+        }
+    }
+
+    /// Returns whether the transmission is paused, see `pause_tx`.
+    pub fn tx_paused(&self) -> bool {
+        self.tx_paused_at_ms.is_some()
+    }
+
+    /// Remembers the last `window` transport frames delivered (ID/control byte, sequence number
+    /// and CRC) and drops the exact duplicates of any of them, for at-most-once delivery, e.g. of
+    /// a frame sent again after a reset. The frames dropped by the port or accept filters are not
+    /// remembered. Application frames carry no sequence number, repeating one is legitimate (e.g.
+    /// a periodic status): they are never deduplicated. 0 (default) disables it.
+    pub fn set_dedup_window(&mut self, window: usize) {
+        self.dedup_window = window;
+        while self.recent_frames.len() > window {
+            self.recent_frames.pop_front();
+        }
+    }
+
+    /// Doubles the retransmit timeout of a frame each time it is sent again, up to
+    /// `max_timeout_ms`, so that a congested link is not flooded with retransmissions.
+    /// `None` (default) keeps the retransmit timeout constant.
+    pub fn set_retransmit_backoff(&mut self, max_timeout_ms: Option<u128>) {
+        self.retransmit_backoff_max_ms = max_timeout_ms;
+    }
+
+    /// Adds a pseudo-random delay of up to `max_jitter_ms` to the retransmit timeouts, so that
+    /// both sides of a link don't retransmit in lockstep. The delays are drawn from a
+    /// `XorShift32` seeded with `seed`: the same seed gives the same delays. 0 (default) disables
+    /// the jitter.
+    pub fn set_retransmit_jitter(&mut self, max_jitter_ms: u128, seed: u32) {
+        self.retransmit_jitter_ms = max_jitter_ms;
+        self.rng = Box::new(XorShift32::new(seed));
+    }
+
+    /// Replaces the source of the retransmit jitter (see `set_retransmit_jitter`), e.g. with a
+    /// hardware RNG.
+    pub fn set_rng<R>(&mut self, rng: R) where R: Rng + 'a {
+        self.rng = Box::new(rng);
+    }
+
+    /// Derives the retransmit timeout from the round-trip times of the frames, as
+    /// `SRTT + 4 * RTTVAR` clamped to `min_ms..=max_ms`, in place of the configured timeout.
+    /// `None` (default) keeps the configured timeout. Until the first ACK is received the
+    /// configured timeout is used.
+    pub fn set_adaptive_retransmit(&mut self, bounds_ms: Option<(u128, u128)>) {
+        self.adaptive_rto_bounds_ms = bounds_ms;
+    }
+
+    /// Returns the smoothed round-trip time of the transport frames, `None` before the first ACK.
+    pub fn srtt_ms(&self) -> Option<u128> {
+        self.srtt_ms
+    }
+
+    /// Returns the base retransmit timeout of the frames sent now, before backoff and jitter.
+    pub fn rto_ms(&self) -> u128 {
+        match (self.adaptive_rto_bounds_ms, self.srtt_ms) {
+            (Some((min_ms, max_ms)), Some(srtt)) => (srtt + 4 * self.rttvar_ms).max(min_ms).min(max_ms.max(min_ms)),
+            _ => self.transport_config.retransmit_timeout_ms,
+        }
+    }
+
+    /// Updates the round-trip time estimate with a new measure, with the gains of RFC 6298.
+    fn rtt_sample(&mut self, rtt: u128) {
+        match self.srtt_ms {
+            Some(srtt) => {
+                let delta = srtt.abs_diff(rtt);
+                self.rttvar_ms = (3 * self.rttvar_ms + delta) / 4;
+                self.srtt_ms = Some((7 * srtt + rtt) / 8);
+            },
+            None => {
+                self.rttvar_ms = rtt / 2;
+                self.srtt_ms = Some(rtt);
+            },
+        }
+    }
+
+    /// Includes a hex dump of the payloads in the debug logs of the frames sent and received.
+    /// Disabled by default: the payloads may hold sensitive data, and formatting them is slow.
+    pub fn set_log_payloads(&mut self, enable: bool) {
+        self.log_payloads = enable;
+    }
+
+    /// `payload_dump` of the frame being received.
+    fn rx_payload_dump(&self) -> String {
+        self.payload_dump(self.rx_frame_payload_buf[0..self.rx_control as usize].iter().copied())
+    }
+
+    /// Hex dump of a payload to append to a debug log, empty unless `log_payloads` is set.
+    fn payload_dump<I>(&self, payload: I) -> String where I: IntoIterator<Item = u8> {
+        if !self.log_payloads {
+            return String::new();
+        }
+        let mut dump = String::from(", payload=[");
+        for byte in payload {
+            dump.push_str(&format!(" 0x{:02x}", byte));
+        }
+        dump.push_str(" ]");
+        dump
+    }
+
+    /// Retains the last `len` raw received bytes for post-mortem debugging, see `rx_trace`.
+    /// 0 (default) disables the trace and releases its memory.
+    pub fn set_rx_trace_len(&mut self, len: usize) {
+        self.rx_trace_len = len;
+        self.rx_trace = Vec::with_capacity(len * 2);
+    }
+
+    /// Returns the last raw received bytes, the oldest first.
+    pub fn rx_trace(&self) -> &[u8] {
+        let start = self.rx_trace.len().saturating_sub(self.rx_trace_len);
+        &self.rx_trace[start..]
+    }
+
+    /// Returns true if the start of a frame was received and the frame is not complete yet, e.g.
+    /// for a read loop to decide whether to wait for more bytes.
+    pub fn has_partial_frame(&self) -> bool {
+        !matches!(self.rx_frame_state, RxState::SearchingForSof)
+    }
+
+    /// Returns the number of raw bytes received of the frame in progress, header and stuff bytes
+    /// included, 0 if no frame is in progress.
+    pub fn partial_frame_bytes(&self) -> usize {
+        if self.has_partial_frame() {
+            self.rx_frame_bytes
+        } else {
+            0
+        }
+    }
+
+    /// Keeps the raw on-wire bytes (header, stuff bytes, checksum and EOF) of the last frame
+    /// received, valid or not, see `last_frame_raw`. Disabled by default as it costs memory.
+    pub fn set_keep_last_frame_raw(&mut self, keep: bool) {
+        self.keep_frame_raw = keep;
+        self.rx_frame_raw.clear();
+        self.last_frame_raw.clear();
+    }
+
+    /// Returns the raw bytes of the last frame received, valid or dropped because of a checksum
+    /// or EOF error. A frame dropped because of a checksum error ends with its checksum.
+    pub fn last_frame_raw(&self) -> &[u8] {
+        &self.last_frame_raw
+    }
+
+    /// Limits the number of application frames accepted per second, frames over the limit are
+    /// dropped and counted. `None` (default) disables the limit.
+    pub fn set_max_rx_frames_per_sec(&mut self, max_frames: Option<u32>) {
+        self.max_rx_frames_per_sec = max_frames;
+        self.rx_rate_window_start_ms = self.clock.now_ms();
+        self.rx_rate_window_frames = 0;
+    }
+
+    /// Sends an application MIN frame on the wire (do not put into the transport queue),
+    /// returning the number of bytes sent or crate::Error.
+    /// # Arguments
+    /// * `id` - Identifier/Control
+    /// * `payload` - data to send
+    /// * `len` - length of payload
+    pub fn send_frame(&mut self, id: u8, payload: &[u8], len: u8) -> Result<u8, Error> {
+        self.send_frame_control(id & 0x3f_u8, payload, len)
+    }
+
+    /// Sends an application MIN frame like `send_frame`, for non-blocking event loops.
+    /// Returns crate::Error::WouldBlock if the frame doesn't fit in the tx buffer right now, and
+    /// crate::Error::FrameTooLarge if it is larger than the whole buffer (`Interface::tx_capacity`).
+    pub fn try_send_frame(&mut self, id: u8, payload: &[u8], len: u8) -> Result<u8, Error> {
+        let size = self.on_wire_size(len);
+        if let Some(capacity) = self.hw_if.tx_capacity() {
+            if size > capacity as u32 {
+                return Err(Error::FrameTooLarge(saturate_u16(size)));
+            }
+        }
+        match self.send_frame(id, payload, len) {
+            Err(Error::NoEnoughTxSpace(_)) => Err(Error::WouldBlock),
+            result => result,
+        }
+    }
+
+    /// Sends an application MIN frame like `send_frame`, the length being taken from `payload`
+    /// (an array, a slice, a `Vec`...).
+    /// Returns crate::Error::PayloadTooLong if the payload doesn't fit in a frame.
+    pub fn send<P: AsRef<[u8]>>(&mut self, id: u8, payload: P) -> Result<u8, Error> {
+        let payload = payload.as_ref();
+        if payload.len() > MAX_PAYLOAD as usize {
+            warn!(target: self.name.as_str(), "payload too long: {} bytes.", payload.len());
+            return Err(Error::PayloadTooLong(payload.len()));
+        }
+        self.send_frame(id, payload, payload.len() as u8)
+    }
+
+    /// Sends an urgent out-of-band application frame right away, ahead of the frames waiting in
+    /// the transport FIFO (e.g. an emergency stop). It is a plain application frame, which the
+    /// other side receives as such even when using the transport protocol: OOB frames are not
+    /// acknowledged and not retransmitted, a lost one is lost for good.
+    pub fn send_oob<P: AsRef<[u8]>>(&mut self, id: u8, payload: P) -> Result<u8, Error> {
+        debug!(target: self.name.as_str(), "send OOB frame: id={}", id & 0x3f);
+        self.send(id, payload)
+    }
+
+    /// Returns the on-wire bytes of an ACK frame acknowledging the frames before `rn`,
+    /// including stuff bytes. Nothing is sent and the transport state is not changed.
+    pub fn encode_ack(&mut self, rn: u8) -> Vec<u8> {
+        self.tx_capture = Some(Vec::new());
+        self.on_wire_bytes(ACK, rn, &[rn], 0, 0xffff, 1);
+        self.tx_capture.take().unwrap_or_default()
+    }
+
+    /// Returns the on-wire bytes of an application frame, including stuff bytes, as `send` would
+    /// send them. Nothing is sent.
+    /// Returns crate::Error::PayloadTooLong if the payload doesn't fit in a frame.
+    pub fn encode_frame(&mut self, id: u8, payload: &[u8]) -> Result<Vec<u8>, Error> {
+        if payload.len() > MAX_PAYLOAD as usize {
+            return Err(Error::PayloadTooLong(payload.len()));
+        }
+        self.tx_capture = Some(Vec::new());
+        self.on_wire_bytes(id & 0x3f_u8, 0, payload, 0, 0xffff, payload.len() as u8);
+        Ok(self.tx_capture.take().unwrap_or_default())
+    }
+
+    /// Writes the on-wire bytes of an application frame into `w` as they are encoded, like
+    /// `encode_frame` but without building the frame in memory first. Returns the number of
+    /// bytes written.
+    ///
+    /// The bytes are written one or two at a time: wrap unbuffered writers (sockets, files) in a
+    /// `std::io::BufWriter`. A payload that doesn't fit in a frame is an `InvalidInput` error.
+    pub fn encode_into<W: Write>(&self, w: &mut W, id: u8, payload: &[u8]) -> io::Result<usize> {
+        if payload.len() > MAX_PAYLOAD as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "payload too long"));
+        }
+        let mut written: usize = 0;
+        let mut result = Ok(());
+        self.wire_format().for_each_wire_byte(id & 0x3f_u8, 0, payload.len() as u8, payload.iter().copied(), |byte| {
+            if result.is_ok() {
+                result = w.write_all(&[byte]);
+                written += 1;
+            }
+        });
+        result.map(|()| written)
+    }
+
+    /// Returns the exact number of bytes (including stuff bytes) `payload` would occupy on the wire,
+    /// as a transport frame if the transport protocol is used, or as an application frame
+    /// otherwise. Nothing is sent.
+    ///
+    /// For a transport frame the sequence number it would get if queued now is assumed.
+    pub fn frame_wire_len(&self, id: u8, payload: &[u8]) -> usize {
+        if self.t_min {
+            let seq = self.transport.sn_min.wrapping_add(self.transport.n_frames);
+            self.on_wire_stuffed_size(id | 0x80_u8, seq, payload)
+        } else {
+            self.on_wire_stuffed_size(id & 0x3f_u8, 0, payload)
+        }
+    }
+
+    /// Sends an application MIN frame with a raw ID/control byte, so that `SPARE_CONTROL_BIT`
+    /// can be set. The transport bit is masked out.
+    /// # Arguments
+    /// * `control` - ID (`0x3f`) and spare bit (`0x40`)
+    /// * `payload` - data to send
+    /// * `len` - length of payload
+    pub fn send_frame_control(&mut self, control: u8, payload: &[u8], len: u8) -> Result<u8, Error> {
+        let avaliable_for_send = self.tx_space(self.on_wire_size(len));
+        if self.on_wire_size(len) <= avaliable_for_send {
+            self.tx_port = self.next_app_tx_port();
+            self.on_wire_bytes(control & 0x7f_u8, 0, payload, 0, 0xffff, len);
+            Ok(len)
+        } else {
+            Err(Error::NoEnoughTxSpace(saturate_u16((len as u32).saturating_sub(avaliable_for_send))))
+        }
+    }
+
+    /// Sends an application MIN frame like `send_frame`, on `port` instead of the context's
+    /// port. The port rotation (see `set_tx_port_rotation`) is left as is.
+    pub fn send_frame_on(&mut self, port: u8, id: u8, payload: &[u8], len: u8) -> Result<u8, Error> {
+        let avaliable_for_send = self.tx_space(self.on_wire_size(len));
+        if self.on_wire_size(len) <= avaliable_for_send {
+            self.tx_port = port;
+            self.on_wire_bytes(id & 0x3f_u8, 0, payload, 0, 0xffff, len);
+            Ok(len)
+        } else {
+            Err(Error::NoEnoughTxSpace(saturate_u16((len as u32).saturating_sub(avaliable_for_send))))
+        }
+    }
+
+    /// Sends an application MIN frame like `send_frame`, taking the payload from a ring buffer
+    /// without copying it: the payload is the `len` bytes from `offset`, wrapping around at the
+    /// end of the ring.
+    /// # Arguments
+    /// * `id` - Identifier/Control
+    /// * `ring` - ring buffer, whose size is a power of 2
+    /// * `offset` - position of the first byte of the payload in the ring
+    /// * `mask` - size of the ring minus 1
+    /// * `len` - length of payload
+    /// # Panics
+    /// Panics if `ring` is shorter than `mask + 1`.
+    pub fn send_frame_ring(&mut self, id: u8, ring: &[u8], offset: u16, mask: u16, len: u8) -> Result<u8, Error> {
+        assert!(ring.len() > mask as usize, "ring of {} bytes shorter than the mask {:#x}", ring.len(), mask);
+        let avaliable_for_send = self.tx_space(self.on_wire_size(len));
+        if self.on_wire_size(len) <= avaliable_for_send {
+            self.tx_port = self.next_app_tx_port();
+            self.on_wire_bytes(id & 0x3f_u8, 0, ring, offset & mask, mask, len);
+            Ok(len)
+        } else {
+            Err(Error::NoEnoughTxSpace(saturate_u16((len as u32).saturating_sub(avaliable_for_send))))
+        }
+    }
+
+    /// Like `send_frame`, but waits for the tx buffer to have enough space, retrying until the
+    /// frame is sent or `timeout_ms` elapses (measured with the context's `Clock`).
+    ///
+    /// It sleeps 1 ms between retries and is intended for low-rate senders of application
+    /// frames, there is no transport FIFO to buffer them.
+    pub fn send_frame_blocking(&mut self, id: u8, payload: &[u8], len: u8, timeout_ms: u128) -> Result<u8, Error> {
+        let start = self.clock.now_ms();
+        loop {
+            match self.send_frame(id, payload, len) {
+                Err(Error::NoEnoughTxSpace(oversize)) => {
+                    if self.clock.now_ms().wrapping_sub(start) >= timeout_ms {
+                        warn!(target: self.name.as_str(), "send frame timeout: oversize={}", oversize);
+                        return Err(Error::NoEnoughTxSpace(oversize));
+                    }
+                    thread::sleep(Duration::from_millis(1));
+                },
+                result => return result,
+            }
+        }
+    }
+
+    /// Returns the ID and payload length of the next transport frame to be sent for the first
+    /// time (the first one beyond the window), if any. See also `frame_wire_len`.
+    pub fn next_unsent(&self) -> Option<(u8, u8)> {
+        let window_size = seq_distance(self.transport.sn_min, self.transport.sn_max);
+        self.transport.frames.get(window_size as usize).map(|frame| (frame.min_id, frame.payload_len))
+    }
+
+    /// Clears the transport FIFO and restarts timing(T-MIN only).
+    /// Returns the number of queued and in-flight frames that were discarded.
+    ///
+    /// If the tx buffer doesn't have room for the RESET frame, it is not sent (see
+    /// `get_reset_send_failure_cnt`) and the local transport is reset all the same.
+    pub fn reset_transport(&mut self, inform_other_side: bool) -> Result<usize, TransportError> {
+        if self.t_min {
+            debug!(target: format!("{}", self.name).as_str(), "reset transport(clear the fifo, restart timing).");
+            if inform_other_side {
+                self.send_reset();
+            }
+            let discarded = self.transport.reset_transport_fifo(self.clock.now_ms());
+            Ok(discarded)
+        } else {
             warn!(target: format!("{}", self.name).as_str(), "no transport support.");
-            Err(String::from("no transport support."))
+            Err(TransportError::NotSupported)
         }
     }
 
+    /// Returns true once an in-sequence frame or an ACK came from the other side since the last
+    /// reset of the transport, i.e. both sides agree on the sequence numbers (T-MIN only).
+    pub fn is_synchronized(&self) -> bool {
+        self.t_min && self.transport.synchronized
+    }
+
+    /// Ends the session(T-MIN only): runs the protocol until all the queued frames are
+    /// acknowledged or `timeout_ms` elapses, then sends a RESET so that the other side knows, and
+    /// resets the transport. The received messages are left for `get_msg`.
+    ///
+    /// Like `recv_frame`, and unlike the other methods, this blocks: it takes a `read` closure,
+    /// called like in `recv_frame` to get the ACKs, so that waiting for the ACKs, the RESET and
+    /// the reset of the transport are one call. Where blocking is not an option, the same
+    /// sequence is to `poll` until `health_check().queued_frames` is 0 or the deadline passes,
+    /// then `reset_transport(true)`.
+    ///
+    /// The context can be used again right away, from fresh sequence numbers: the other side
+    /// reset too.
+    /// Returns a `TransportError` like the other transport operations:
+    /// crate::TransportError::NotSupported without the transport protocol, and
+    /// crate::TransportError::Timeout with the number of frames discarded if they were not all
+    /// acknowledged in time; the RESET is sent all the same.
+    pub fn shutdown<R>(&mut self, mut read: R, timeout_ms: u128) -> Result<(), TransportError> where R: FnMut(&mut [u8]) -> usize {
+        if !self.t_min {
+            warn!(target: self.name.as_str(), "no transport support.");
+            return Err(TransportError::NotSupported);
+        }
+        let start = self.clock.now_ms();
+        let mut buf = [0_u8; MAX_PAYLOAD as usize + 1];
+        while !self.transport.frames.is_empty() && self.clock.now_ms().wrapping_sub(start) < timeout_ms {
+            let len = read(&mut buf).min(buf.len());
+            self.poll(&buf, len as u32);
+            if len == 0 {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        debug!(target: self.name.as_str(), "shutdown, {} frames not acknowledged.", self.transport.frames.len());
+        self.send_reset();
+        match self.transport.reset_transport_fifo(self.clock.now_ms()) {
+            0 => Ok(()),
+            discarded => Err(TransportError::Timeout(discarded)),
+        }
+    }
+
+    /// Queues a MIN ID / payload frame into the outgoing FIFO(T-MIN only)
+    /// Returns crate::TransportError::NotSupported if context doesn't support transport protocol,
+    /// or crate::TransportError::FrameTooLargeForTxBuffer if the frame can never fit the tx
+    /// buffer (see `set_reject_frames_over_tx_space`).
+    pub fn queue_frame(&mut self, id: u8, payload: &[u8], len: u8) -> Result<(), TransportError> {
+        if self.t_min {
+            self.check_fits_tx_buffer(len)?;
+            let frame = TransportFrame::new(id, payload, len);
+            if self.push(frame) {
+                Ok(())
+            } else {
+                Err(TransportError::FifoFull(0))
+            }
+        } else {
+            warn!(target: format!("{}", self.name).as_str(), "no transport support.");
+            Err(TransportError::NotSupported)
+        }
+    }
+
+    fn check_fits_tx_buffer(&self, len: u8) -> Result<(), TransportError> {
+        if self.reject_frames_over_tx_space {
+            let size = self.on_wire_size(len);
+            if size > self.hw_tx_space() as u32 {
+                warn!(target: self.name.as_str(), "frame of {} bytes larger than the tx buffer.", size);
+                return Err(TransportError::FrameTooLargeForTxBuffer(saturate_u16(size)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends a ping to measure the round-trip time of the link(T-MIN only).
+    ///
+    /// The ping is an empty transport frame with the ID `PING_ID`, the other side delivers it as
+    /// an ordinary message that the application can ignore. The round-trip time runs from the
+    /// last time the frame was sent until its ACK is received, so it is only meaningful if the
+    /// other side polls often enough to acknowledge promptly: the ACK of an in-order frame is
+    /// sent as soon as it is received, unless it is throttled (see `set_ack_throttle`).
+    /// Returns crate::TransportError::NotSupported if context doesn't support transport protocol.
+    pub fn ping(&mut self) -> Result<PingToken, TransportError> {
+        if self.t_min {
+            let token = self.next_ping;
+            self.next_ping = self.next_ping.wrapping_add(1);
+            let mut frame = TransportFrame::new(PING_ID, &[], 0);
+            frame.ping = Some(token);
+            if self.push(frame) {
+                Ok(PingToken(token))
+            } else {
+                Err(TransportError::FifoFull(0))
+            }
+        } else {
+            warn!(target: self.name.as_str(), "no transport support.");
+            Err(TransportError::NotSupported)
+        }
+    }
+
+    /// Takes the round-trip time in milliseconds of a ping, `None` if it is not acknowledged yet
+    /// or was already taken. A ping discarded by a reset of the transport is never resolved.
+    pub fn take_rtt(&mut self, token: PingToken) -> Option<u128> {
+        self.ping_rtts.remove(&token.0)
+    }
+
+    /// Returns the longest time a transport frame waited for its ACK, from the last time it was
+    /// sent, since the last `reset_max_ack_latency`. `None` if no frame was acknowledged since.
+    pub fn max_ack_latency_ms(&self) -> Option<u128> {
+        self.max_ack_latency_ms
+    }
+
+    /// Starts measuring `max_ack_latency_ms` again, e.g. at the start of a monitoring period.
+    pub fn reset_max_ack_latency(&mut self) {
+        self.max_ack_latency_ms = None;
+    }
+
+    /// Returns the oldest frame sent and not acknowledged yet, the one holding up the window,
+    /// `None` if no frame is in flight.
+    pub fn oldest_unacked(&self) -> Option<FrameInfo> {
+        if self.transport.sn_min == self.transport.sn_max {
+            return None;
+        }
+        self.transport.frames.front().map(|frame| FrameInfo {
+            min_id: frame.min_id,
+            seq: frame.seq,
+            payload_len: frame.payload_len,
+            in_flight: true,
+            retransmits: frame.retransmits,
+        })
+    }
+
+    /// Returns how many times the oldest frame in flight was sent again, a direct sign of link
+    /// trouble, see `oldest_unacked` for its ID and sequence number. `None` if no frame is in flight.
+    pub fn oldest_unacked_attempts(&self) -> Option<u8> {
+        self.oldest_unacked().map(|frame| frame.retransmits)
+    }
+
+    /// Queues a batch of MIN ID / payload frames into the outgoing FIFO(T-MIN only), either all
+    /// of them or none: a batch that doesn't fit in the room left in the FIFO (see
+    /// `fifo_capacity`) is rejected as a whole, so that related frames are never split.
+    /// Returns the number of frames queued, or crate::TransportError::FifoFull with the number
+    /// of frames that would have fit.
+    pub fn queue_frames(&mut self, frames: &[(u8, &[u8])]) -> Result<usize, TransportError> {
+        if !self.t_min {
+            warn!(target: self.name.as_str(), "no transport support.");
+            return Err(TransportError::NotSupported);
+        }
+        if let Some((_, payload)) = frames.iter().find(|(_, payload)| payload.len() > MAX_PAYLOAD as usize) {
+            return Err(TransportError::PayloadTooLong(payload.len()));
+        }
+        for (_, payload) in frames {
+            self.check_fits_tx_buffer(payload.len() as u8)?;
+        }
+        let room = self.fifo_capacity().saturating_sub(self.transport.n_frames as usize);
+        if frames.len() > room {
+            warn!(target: self.name.as_str(), "no room for {} frames in the fifo: room={}", frames.len(), room);
+            return Err(TransportError::FifoFull(room));
+        }
+        for (id, payload) in frames {
+            // Can't fail, the transport protocol is used
+            let _ = self.queue_frame(*id, payload, payload.len() as u8);
+        }
+        Ok(frames.len())
+    }
+
     /// sends received bytes into a MIN context and runs the transport timeouts.
     pub fn poll(&mut self, buf: &[u8], buf_len: u32) {
-        for i in 0..buf_len {
-            self.rx_byte(buf[i as usize]);
+        self.receive(&buf[0..buf_len as usize]);
+        self.tick();
+        let over_window = self.t_min && self.seq_space_pressure() > self.transport_config.window_size as f32 / 256.0;
+        if over_window && !self.seq_space_warned {
+            warn!(target: self.name.as_str(), "frames in flight span {} of the sequence space, over the window of {} frames (sn_min={}, sn_max={}).",
+                self.seq_space_pressure(), self.transport_config.window_size, self.transport.sn_min, self.transport.sn_max);
+        }
+        self.seq_space_warned = over_window;
+    }
+
+    /// Returns the share of the 256 sequence numbers spanned by the frames in flight.
+    ///
+    /// `TransportConfig::validate` caps the window at 16 frames, 1/16 of the sequence space, far
+    /// from ambiguous ACKs: a configured window can't be too large. What
+    /// can go wrong is the window actually used, so `poll` checks the frames in flight after each
+    /// pass and logs a warning once when they span more than the configured window, e.g. when a
+    /// running context was given a smaller window. The `debug-checks` feature asserts it instead.
+    pub fn seq_space_pressure(&self) -> f32 {
+        seq_distance(self.transport.sn_min, self.transport.sn_max) as f32 / 256.0
+    }
+
+    /// Feeds received bytes into the receiving state machine, without running the transport timeouts.
+    pub fn receive(&mut self, buf: &[u8]) {
+        self.tx_batch(|min| {
+            for &byte in buf {
+                min.rx_byte(byte);
+            }
+            min.flush_pending_ack();
+            min.apply_reactions();
+        })
+    }
+
+    /// Feeds bytes received on `port` like `receive`, e.g. from a bus shared by several ports. The
+    /// frames completed by these bytes are delivered with `port` (see `Msg::port`) and checked
+    /// against `set_accepted_ports`.
+    pub fn receive_on(&mut self, port: u8, buf: &[u8]) {
+        self.rx_port = port;
+        self.receive(buf);
+        self.rx_port = self.port;
+    }
+
+    /// Hands a frame to the receiving half of the protocol as if it had just been received, without
+    /// going through the bytes and the CRC. `id_control` is the ID/control byte (e.g. `0xff` for an
+    /// ACK, `0xfe` for a RESET, `0x80 | id` for a transport frame), `seq` the sequence number.
+    /// The message is delivered with a `crc` of 0. Returns `TransportError::PayloadTooLong` if
+    /// `payload` doesn't fit in a frame, nothing is received then.
+    ///
+    /// For testing the transport handling (ACKs, NACKs, RESETs, sequence numbers) in isolation.
+    /// Integration tests and the tests of other crates can't see `cfg(test)`, so it is also
+    /// compiled with the `test-util` feature: enable it in `[dev-dependencies]` only, it is not
+    /// part of the API of a normal build.
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn inject_frame(&mut self, id_control: u8, seq: u8, payload: &[u8]) -> Result<(), TransportError> {
+        if payload.len() > MAX_PAYLOAD as usize {
+            return Err(TransportError::PayloadTooLong(payload.len()));
+        }
+        self.tx_batch(|min| {
+            min.rx_frame_id_control = id_control;
+            min.rx_frame_seq = seq;
+            min.rx_frame_length = payload.len() as u8;
+            min.rx_control = payload.len() as u8;
+            min.rx_frame_payload_buf[..payload.len()].copy_from_slice(payload);
+            min.rx_frame_checksum = 0;
+            min.valid_frame_received();
+            min.flush_pending_ack();
+            min.apply_reactions();
+        });
+        Ok(())
+    }
+
+    /// Runs `bytes` through the receiving state machine and returns the fields of the frame it
+    /// parsed, as far as it got, valid or not: see `test_util::assert_frame_eq`.
+    #[cfg(feature = "test-util")]
+    pub(crate) fn parse_frame(&mut self, bytes: &[u8]) -> crate::test_util::FrameFields {
+        let mut fields = crate::test_util::FrameFields::default();
+        for &byte in bytes {
+            let state = self.rx_frame_state;
+            let payload_bytes = self.rx_frame_payload_bytes;
+            self.rx_byte(byte);
+            if self.rx_frame_state == RxState::ReceivingIdControl && state != RxState::ReceivingIdControl {
+                // A new frame starts, the bytes before were not one
+                fields = crate::test_util::FrameFields { started: true, ..Default::default() };
+                continue;
+            }
+            match state {
+                RxState::ReceivingIdControl if self.rx_frame_state != state => fields.id_control = Some(self.rx_frame_id_control),
+                RxState::ReceivingSeq if self.rx_frame_state != state => fields.seq = Some(self.rx_frame_seq),
+                RxState::ReceivingLength if self.rx_frame_state != state => fields.length = Some(self.rx_control),
+                RxState::ReceivingPayload if self.rx_frame_payload_bytes != payload_bytes => fields.payload.push(byte),
+                RxState::ReceivingChecksum0 if self.rx_frame_state != state => fields.checksum = Some(self.rx_frame_checksum),
+                RxState::ReceivingEof => fields.eof = Some(byte),
+                _ => {},
+            }
+        }
+        fields.error = self.error_queue.drain(..).last();
+        fields
+    }
+
+    /// Feeds a whole datagram holding one frame, see `set_datagram_mode`. A datagram shorter or
+    /// longer than the frame it holds is dropped as a `FrameError::Length`.
+    pub fn receive_datagram(&mut self, datagram: &[u8]) {
+        self.tx_batch(|min| min.receive_datagram_batch(datagram))
+    }
+
+    fn receive_datagram_batch(&mut self, datagram: &[u8]) {
+        self.rx_bytes_total = self.rx_bytes_total.wrapping_add(datagram.len() as u64);
+        self.rx_frame_state = RxState::ReceivingIdControl;
+        self.rx_frame_start_ms = self.clock.now_ms();
+        self.rx_control = 0;
+        if self.keep_frame_raw {
+            self.rx_frame_raw.clear();
+            self.rx_frame_raw.extend_from_slice(datagram);
+        }
+        for (i, &byte) in datagram.iter().enumerate() {
+            self.rx_frame_byte(byte);
+            if matches!(self.rx_frame_state, RxState::SearchingForSof) {
+                // Dropped
+                break;
+            }
+            if matches!(self.rx_frame_state, RxState::ReceivingEof) {
+                if i + 1 == datagram.len() {
+                    // There is no EOF byte in a datagram
+                    self.rx_frame_byte(EOF_BYTE);
+                }
+                break;
+            }
+        }
+        if !matches!(self.rx_frame_state, RxState::SearchingForSof) {
+            warn!(target: self.name.as_str(), "datagram length error, drop this frame.");
+            self.record_recent_error(RxError::Length);
+            self.push_frame_error(FrameError::Length { claimed: self.rx_control });
+            self.hw_if.on_rx_error();
+            self.rx_frame_state = RxState::SearchingForSof;
+        }
+        self.flush_pending_ack();
+        self.apply_reactions();
+    }
+
+    /// Runs the transport timeouts: sends new frames, retransmits old ones and sends ACKs.
+    /// Returns what was sent.
+    pub fn tick(&mut self) -> TickReport {
+        self.tx_batch(|min| min.tick_batch())
+    }
+
+    fn tick_batch(&mut self) -> TickReport {
+        let mut report = TickReport::default();
+
+        // Give up on a frame which never completes (e.g. the other side went silent mid-payload)
+        if let Some(timeout) = self.rx_frame_timeout_ms {
+            if !matches!(self.rx_frame_state, RxState::SearchingForSof)
+                && self.clock.now_ms().wrapping_sub(self.rx_frame_start_ms) >= timeout {
+                warn!(target: self.name.as_str(), "rx frame timeout, drop this frame.");
+                self.rx_frame_state = RxState::SearchingForSof;
+                self.rx_frame_timeouts = self.rx_frame_timeouts.wrapping_add(1);
+            }
         }
 
         // for T-MIN
-        if self.t_min {
-            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(std::time::Duration::from_secs(0)).as_millis();
+        // Nothing is sent while the physical link is down, the timers are left untouched so that
+        // retransmission resumes as usual once the link is back. Nor while paused, see `pause_tx`.
+        if self.t_min && !self.listen_only && self.tx_paused_at_ms.is_none() && self.hw_if.link_up() {
+            let now = self.clock.now_ms();
             let mut remote_connected = false;
             let mut remote_active = false;
-            if now.wrapping_sub(self.transport.last_received_anything_ms) < TRANSPORT_IDLE_TIMEOUT_MS {
+            if now.wrapping_sub(self.transport.last_received_anything_ms) < self.transport_config.idle_timeout_ms {
                 remote_connected = true;
             }
-            if now.wrapping_sub(self.transport.last_received_frame_ms) < TRANSPORT_IDLE_TIMEOUT_MS {
+            if now.wrapping_sub(self.transport.last_received_frame_ms) < self.transport_config.idle_timeout_ms {
                 remote_active = true;
             }
-            let window_size = self.transport.sn_max.wrapping_sub(self.transport.sn_min);
-            if (window_size < TRANSPORT_MAX_WINDOW_SIZE) && (self.transport.n_frames > window_size) {
+            let window_size = seq_distance(self.transport.sn_min, self.transport.sn_max);
+            if (window_size < self.transport_config.window_size) && (self.transport.n_frames > window_size) {
                 debug!(target: format!("{}", self.name).as_str(), "Send new frames(window_size={}, sn_max={}, sn_min={}, n_frames={})",
                     window_size, self.transport.sn_max, self.transport.sn_min, self.transport.n_frames
                 );
                 // There are new frames we can send; but don't even bother if there's no buffer space for them
-                self.transport_fifo_frame_send(window_size as usize, true);
-                self.transport.sn_max = self.transport.sn_max.wrapping_add(1);
+                // A frame not sent stays out of the window, it is sent as new at the next tick
+                if self.transport_fifo_frame_send(window_size as usize, true) {
+                    report.frames_sent += 1;
+                    self.transport.sn_max = self.transport.sn_max.wrapping_add(1);
+                }
+                self.check_transport_invariants();
             } else {
                 // Sender cannot send new frames so resend old ones (if there's anyone there)
                 if (window_size > 0) && remote_connected {
                     // There are unacknowledged frames. Can re-send an old frame. Pick the least recently sent one.
                     let (index, last_sent_time_ms) = self.find_retransmit_frame();
-                    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(std::time::Duration::from_secs(0)).as_millis();
-                    if now.wrapping_sub(last_sent_time_ms) >= TRANSPORT_FRAME_RETRANSMIT_TIMEOUT_MS {
+                    let retransmit_timeout_ms = self.transport.frames.get(index)
+                        .map_or(self.transport_config.retransmit_timeout_ms, |frame| frame.retransmit_timeout_ms);
+                    let now = self.clock.now_ms();
+                    if now.wrapping_sub(last_sent_time_ms) >= retransmit_timeout_ms {
                         debug!(target: format!("{}", self.name).as_str(), "Send old frames(window_size={}, sn_max={}, sn_min={}, n_frames={})",
                             window_size, self.transport.sn_max, self.transport.sn_min, self.transport.n_frames
                         );
-                        self.transport_fifo_frame_send(index, false);
+                        if self.transport_fifo_frame_send(index, false) {
+                            report.frames_retransmitted += 1;
+                            self.transport.retransmits_timeout = self.transport.retransmits_timeout.wrapping_add(1);
+                        }
                     }
                 }
             }
     
             // 发送 ack
-            if now.wrapping_sub(self.transport.last_sent_ack_time_ms) > TRANSPORT_ACK_RETRANSMIT_TIMEOUT_MS {
-                if remote_active {
-                    self.send_ack();
+            if now.wrapping_sub(self.transport.last_sent_ack_time_ms) > self.get_ack_delay_ms() {
+                if remote_active && self.send_ack() {
+                    report.acks_sent += 1;
                 }
             }
         }
+        report
+    }
+
+    /// Feeds received bytes, runs the transport timeouts and returns all the messages received
+    /// so far, in order. This is `receive`, `tick` and `drain_msgs` in one call.
+    pub fn process(&mut self, buf: &[u8]) -> Vec<Msg> {
+        self.receive(buf);
+        self.tick();
+        self.drain_msgs().collect()
+    }
+
+    /// Takes all the received messages, in order.
+    pub fn drain_msgs(&mut self) -> Drain<'_, Msg> {
+        self.msg_queue.drain(..)
+    }
+
+    /// Takes why the frames received since the last call were dropped, in order, up to the last
+    /// `MAX_FRAME_ERRORS`. Like `drain_msgs`, meant to be called from the polling loop.
+    pub fn drain_errors(&mut self) -> Drain<'_, FrameError> {
+        self.error_queue.drain(..)
+    }
+
+    /// Takes the ID and sequence number of the transport frames acknowledged by the other side
+    /// so far, in order. They pile up until taken: call it alongside `drain_msgs`.
+    pub fn drain_acked(&mut self) -> Drain<'_, (u8, u8)> {
+        self.acked_queue.drain(..)
+    }
+
+    /// Iterates over the received messages, taking them one at a time until there is none left.
+    /// It doesn't block: it neither feeds new bytes nor runs the transport timeouts.
+    pub fn messages(&mut self) -> Messages<'_, 'a, T> {
+        Messages {
+            context: self,
+        }
     }
 
     pub fn get_msg(&mut self) -> Result<Msg, Error> {
@@ -581,6 +2676,67 @@ impl<'a, T> Context<'a, T> where T: crate::Interface{
             }
         }
     }
+
+    /// Blocking convenience wrapping the usual read / `poll` / `get_msg` loop: reads bytes with
+    /// `read` and feeds them to `poll` until a message is decoded, and returns the first one.
+    ///
+    /// `read` fills the buffer it is given and returns the number of bytes read, 0 if none were
+    /// available; it may block for a while (e.g. a serial port read timeout). It sleeps 1 ms
+    /// when nothing was read.
+    /// Returns crate::Error::NoMsg if no message arrived within `timeout_ms` (measured with the
+    /// context's `Clock`). A message already waiting is returned right away.
+    pub fn recv_frame<R>(&mut self, mut read: R, timeout_ms: u128) -> Result<Msg, Error> where R: FnMut(&mut [u8]) -> usize {
+        let start = self.clock.now_ms();
+        let mut buf = [0_u8; MAX_PAYLOAD as usize + 1];
+        loop {
+            if let Some(msg) = self.msg_queue.pop_front() {
+                return Ok(msg);
+            }
+            if self.clock.now_ms().wrapping_sub(start) >= timeout_ms {
+                return Err(Error::NoMsg);
+            }
+            let len = read(&mut buf).min(buf.len());
+            self.poll(&buf, len as u32);
+            if len == 0 {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+    }
+
+    /// Blocks like `recv_frame` until a message matching `pred` is received, and returns it.
+    ///
+    /// The messages not matching are left in the queue, in order, for `get_msg`/`drain_msgs`.
+    /// Returns crate::Error::NoMsg if no matching message arrived within `timeout_ms`.
+    pub fn process_until<R, P>(&mut self, mut read: R, pred: P, timeout_ms: u128) -> Result<Msg, Error>
+        where R: FnMut(&mut [u8]) -> usize, P: Fn(&Msg) -> bool {
+        let start = self.clock.now_ms();
+        let mut buf = [0_u8; MAX_PAYLOAD as usize + 1];
+        loop {
+            // The whole queue is checked again: messages may have been dropped from its front
+            // by the overflow policy, shifting the others
+            if let Some(i) = self.msg_queue.iter().position(&pred) {
+                if let Some(msg) = self.msg_queue.remove(i) {
+                    return Ok(msg);
+                }
+            }
+            if self.clock.now_ms().wrapping_sub(start) >= timeout_ms {
+                return Err(Error::NoMsg);
+            }
+            let len = read(&mut buf).min(buf.len());
+            self.poll(&buf, len as u32);
+            if len == 0 {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+    }
+    /// Discards all the received messages not yet taken by `get_msg`, returns how many were discarded.
+    pub fn clear_msgs(&mut self) -> usize {
+        let discarded = self.msg_queue.len();
+        self.msg_queue.clear();
+        self.discarded_msgs = self.discarded_msgs.wrapping_add(discarded as u32);
+        discarded
+    }
+
     pub fn get_rx_checksum(&self) -> u32 {
         self.rx_checksum.finalize()
     }
@@ -601,7 +2757,281 @@ impl<'a, T> Context<'a, T> where T: crate::Interface{
         self.transport.get_spurious_ack_cnt()
     }
 
+    /// Number of frames sent again because their ACK didn't come in time
+    pub fn get_retransmit_timeout_cnt(&self) -> u32 {
+        self.transport.get_retransmit_timeout_cnt()
+    }
+
+    /// Number of frames sent again because the other side asked for them (NACK)
+    pub fn get_retransmit_nack_cnt(&self) -> u32 {
+        self.transport.get_retransmit_nack_cnt()
+    }
+
     pub fn get_drop_cnt(&self) -> u32 {
         self.transport.get_drop_cnt()
     }
+
+    pub fn get_rate_limited_drop_cnt(&self) -> u32 {
+        self.rate_limited_drops
+    }
+
+    pub fn get_discarded_msg_cnt(&self) -> u32 {
+        self.discarded_msgs
+    }
+
+    pub fn get_crc_error_cnt(&self) -> u32 {
+        self.crc_errors
+    }
+
+    pub fn get_eof_error_cnt(&self) -> u32 {
+        self.eof_errors
+    }
+
+    pub fn get_header_crc_error_cnt(&self) -> u32 {
+        self.header_crc_errors
+    }
+
+    pub fn get_transport_unsupported_drop_cnt(&self) -> u32 {
+        self.transport_unsupported_drops
+    }
+
+    /// Returns the number of received messages dropped because the message queue was full, see
+    /// `set_msg_queue_capacity`.
+    pub fn get_msg_overflow_drop_cnt(&self) -> u32 {
+        self.msg_overflow_drops
+    }
+
+    /// Returns the number of frames not queued because the transport FIFO was full.
+    pub fn get_fifo_overflow_drop_cnt(&self) -> u32 {
+        self.fifo_overflow_drops
+    }
+
+    pub fn get_deduped_frame_cnt(&self) -> u32 {
+        self.deduped_frames
+    }
+
+    pub fn get_filtered_drop_cnt(&self) -> u32 {
+        self.filtered_drops
+    }
+
+    pub fn get_port_filtered_drop_cnt(&self) -> u32 {
+        self.port_filtered_drops
+    }
+
+    /// Returns the number of replies returned by the callbacks and then dropped, see `Reaction::QueueReply`.
+    pub fn get_dropped_reply_cnt(&self) -> u32 {
+        self.dropped_replies
+    }
+
+    /// Returns the number of NACKs sent for transport frames failing the checksum, see
+    /// `set_nack_on_crc_error`.
+    pub fn get_nack_sent_cnt(&self) -> u32 {
+        self.nacks_sent
+    }
+
+    /// Returns the number of ACKs not sent because the tx buffer was short of space. The frames
+    /// they acknowledge are acknowledged by a later ACK.
+    pub fn get_ack_send_failure_cnt(&self) -> u32 {
+        self.ack_send_failures
+    }
+
+    /// Returns the number of RESETs not sent because the tx buffer was short of space, see
+    /// `reset_transport`.
+    pub fn get_reset_send_failure_cnt(&self) -> u32 {
+        self.reset_send_failures
+    }
+
+    /// Returns the receive errors of the last `ERROR_HISTOGRAM_WINDOW_MS` (60 s), to tell a
+    /// transient glitch from a sustained problem. The errors are counted by buckets of 10 s:
+    /// the window slides by 10 s steps and covers 50 to 60 s.
+    pub fn recent_error_histogram(&self) -> ErrorHistogram {
+        let epoch = self.clock.now_ms() / ERROR_HISTOGRAM_BUCKET_MS;
+        let mut histogram = ErrorHistogram::default();
+        for (bucket_epoch, counts) in self.error_buckets.iter() {
+            if *bucket_epoch <= epoch && epoch - bucket_epoch < ERROR_HISTOGRAM_BUCKETS as u128 {
+                histogram.crc_errors = histogram.crc_errors.wrapping_add(counts.crc_errors);
+                histogram.length_errors = histogram.length_errors.wrapping_add(counts.length_errors);
+                histogram.sequence_mismatches = histogram.sequence_mismatches.wrapping_add(counts.sequence_mismatches);
+                histogram.eof_errors = histogram.eof_errors.wrapping_add(counts.eof_errors);
+            }
+        }
+        histogram
+    }
+
+    /// Returns the number of payload bytes sent, ACK and RESET frames included.
+    pub fn get_payload_bytes_sent_cnt(&self) -> u64 {
+        self.payload_bytes_sent
+    }
+
+    /// Returns the number of bytes sent on the wire, framing and stuff bytes included.
+    pub fn get_wire_bytes_sent_cnt(&self) -> u64 {
+        self.wire_bytes_sent
+    }
+
+    /// Returns the number of bytes received, whether they make valid frames or not.
+    pub fn rx_bytes_total(&self) -> u64 {
+        self.rx_bytes_total
+    }
+
+    /// Returns the number of valid frames received, ACKs and RESETs included.
+    pub fn rx_frames_total(&self) -> u64 {
+        self.rx_frames_total
+    }
+
+    /// Returns the bytes sent on top of the payloads (framing and stuff bytes), relative to the
+    /// payload bytes sent: 0.5 means the wire carried 1.5 bytes per payload byte. Payloads heavy
+    /// in `0xaa` raise it through the stuff bytes. 0 if no payload was sent yet.
+    pub fn stuffing_overhead_ratio(&self) -> f32 {
+        if self.payload_bytes_sent == 0 {
+            return 0.0;
+        }
+        self.wire_bytes_sent.saturating_sub(self.payload_bytes_sent) as f32 / self.payload_bytes_sent as f32
+    }
+
+    /// Returns the current ACK delay, which depends on the message queue when the ACKs are
+    /// throttled (see `set_ack_throttle`).
+    pub fn get_ack_delay_ms(&self) -> u128 {
+        match self.ack_throttle_max_ms {
+            Some(max_delay) if max_delay > self.transport_config.ack_timeout_ms => {
+                // Full at the configured bound, so that a small queue is throttled before it overflows
+                let capacity = self.msg_queue_capacity.unwrap_or(MAX_MSG as usize).max(1);
+                let queued = self.msg_queue.len().min(capacity) as u128;
+                let ack_timeout_ms = self.transport_config.ack_timeout_ms;
+                ack_timeout_ms + (max_delay - ack_timeout_ms) * queued / capacity as u128
+            },
+            _ => self.transport_config.ack_timeout_ms,
+        }
+    }
+
+    pub fn get_rx_frame_timeout_cnt(&self) -> u32 {
+        self.rx_frame_timeouts
+    }
+
+    /// Returns the statistics of the transport protocol.
+    pub fn get_transport_stats(&self) -> TransportStats {
+        self.transport.get_stats()
+    }
+
+    /// Returns a snapshot of the state of the link, for monitoring.
+    pub fn health_check(&self) -> LinkHealth {
+        let now = self.clock.now_ms();
+        let in_flight = seq_distance(self.transport.sn_min, self.transport.sn_max);
+        let crc_error_rate = if self.rx_crc_history_len > 0 {
+            self.rx_crc_history.count_ones() as f32 / self.rx_crc_history_len as f32
+        } else {
+            0.0
+        };
+        LinkHealth {
+            link_up: self.hw_if.link_up(),
+            in_flight,
+            queued_frames: self.transport.n_frames,
+            pending_msgs: self.msg_queue.len(),
+            ms_since_last_rx: self.last_rx_frame_ms.map(|last| now.wrapping_sub(last)),
+            crc_error_rate,
+            tx_stalled: self.t_min && in_flight > 0
+                && now.wrapping_sub(self.transport.last_received_anything_ms) >= self.transport_config.idle_timeout_ms,
+        }
+    }
+
+    /// Returns whether a frame queued now fits in the transport FIFO and in the sending window
+    /// (T-MIN only): the FIFO is not full and the frames in it, `queued_frames` of `health_check`
+    /// (the `in_flight` ones included), leave room in the window. It is not necessarily sent at
+    /// the next `tick`: each `tick` sends at most one new frame, the ones queued before it first.
+    ///
+    /// Checked after `poll` or `tick`, it tells a producer when to call `queue_frame` next.
+    pub fn can_queue(&self) -> bool {
+        self.t_min
+            && self.transport.n_frames < self.transport_config.fifo_capacity
+            && self.transport.n_frames < self.transport_config.window_size
+    }
+
+    /// Returns the sequence numbers of the transport protocol, to be restored later with
+    /// `Context::from_transport_state`.
+    pub fn get_transport_state(&self) -> TransportState {
+        self.transport.get_state()
+    }
+
+    /// Returns a read-only view of the queues and state of the context, e.g. to render a live
+    /// dashboard from a debugger. Nothing is copied until asked for.
+    pub fn inspect(&self) -> ContextView<'_, 'a, T> {
+        ContextView {
+            context: self,
+        }
+    }
+}
+
+/// Metadata of a frame in the transport FIFO, see `ContextView::frames`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FrameInfo {
+    pub min_id: u8,
+    /// Sequence number, only meaningful once the frame was sent
+    pub seq: u8,
+    pub payload_len: u8,
+    /// Sent and not acknowledged yet
+    pub in_flight: bool,
+    /// Number of times the frame was sent again
+    pub retransmits: u8,
+}
+
+/// Read-only view of a context, see `Context::inspect`.
+pub struct ContextView<'c, 'a, T> where T: crate::Interface {
+    context: &'c Context<'a, T>,
+}
+
+impl<'c, 'a, T> ContextView<'c, 'a, T> where T: crate::Interface {
+    /// The received messages not taken yet, the oldest first.
+    pub fn msgs(&self) -> vec_deque::Iter<'c, Msg> {
+        self.context.msg_queue.iter()
+    }
+
+    /// The frames of the transport FIFO, the in-flight ones first.
+    pub fn frames(&self) -> impl Iterator<Item = FrameInfo> + 'c {
+        let in_flight = seq_distance(self.context.transport.sn_min, self.context.transport.sn_max) as usize;
+        self.context.transport.frames.iter().enumerate().map(move |(i, frame)| FrameInfo {
+            min_id: frame.min_id,
+            seq: frame.seq,
+            payload_len: frame.payload_len,
+            in_flight: i < in_flight,
+            retransmits: frame.retransmits,
+        })
+    }
+
+    /// State of the receiving state machine.
+    pub fn rx_state(&self) -> RxState {
+        self.context.rx_frame_state
+    }
+
+    pub fn transport_state(&self) -> TransportState {
+        self.context.get_transport_state()
+    }
+
+    pub fn transport_stats(&self) -> TransportStats {
+        self.context.get_transport_stats()
+    }
+
+    pub fn health(&self) -> LinkHealth {
+        self.context.health_check()
+    }
+}
+
+/// Iterator over the received messages, see `Context::messages`.
+pub struct Messages<'c, 'a, T> where T: crate::Interface {
+    context: &'c mut Context<'a, T>,
+}
+
+impl<'c, 'a, T> Iterator for Messages<'c, 'a, T> where T: crate::Interface {
+    type Item = Msg;
+
+    fn next(&mut self) -> Option<Msg> {
+        self.context.msg_queue.pop_front()
+    }
+}
+
+impl<'c, 'a, T> IntoIterator for &'c mut Context<'a, T> where T: crate::Interface {
+    type Item = Msg;
+    type IntoIter = Messages<'c, 'a, T>;
+
+    fn into_iter(self) -> Messages<'c, 'a, T> {
+        self.messages()
+    }
 }
@@ -1,22 +1,99 @@
 const CRC_POLYNOMIAL_NORMAL: u32 = 0x04c11db7;
 const CRC_POLYNOMIAL_REVERSED: u32 = 0xedb88320;
 
+/// CRC-32 of `bytes` as used by the MIN frames (reversed, initial value and final XOR
+/// `0xffffffff`), usable in constants. Same result as `Crc32Context::new(0xffffffff, true,
+/// false, false, 0xffffffff)` fed with `bytes`.
+pub const fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc: u32 = 0xffffffff;
+    let mut i = 0;
+    while i < bytes.len() {
+        crc = crc32_step(crc, bytes[i]);
+        i += 1;
+    }
+    crc ^ 0xffffffff
+}
+
+/// Feeds a byte to a running `crc32`, before the final XOR.
+pub(crate) const fn crc32_step(mut crc: u32, byte: u8) -> u32 {
+    crc ^= byte as u32;
+    let mut bit = 0;
+    while bit < 8 {
+        if crc & 1 == 1 {
+            crc = (crc >> 1) ^ CRC_POLYNOMIAL_REVERSED;
+        } else {
+            crc >>= 1;
+        }
+        bit += 1;
+    }
+    crc
+}
+
+/// Feeds a byte to a running CRC-8 (polynomial `0x07`, initial value `0`), used for the
+/// header checksum.
+#[cfg(feature = "std")]
+pub(crate) const fn crc8_step(mut crc: u8, byte: u8) -> u8 {
+    crc ^= byte;
+    let mut bit = 0;
+    while bit < 8 {
+        if crc & 0x80 == 0x80 {
+            crc = (crc << 1) ^ 0x07;
+        } else {
+            crc <<= 1;
+        }
+        bit += 1;
+    }
+    crc
+}
+
 pub struct Crc32Context {
     crc: u32,
+    // Polynomial in the bit order used by the step function
+    polynomial: u32,
     // Reversed or Normal
     reversed: bool,
     refin: bool,
     refout: bool,
+    // XORed with the result, 0xffffffff inverts it
+    final_xor: u32,
 }
 
 impl Crc32Context {
-    pub fn new(crc: u32, reversed: bool, refin: bool, refout: bool) -> Self {
+    /// Creates a context for CRC-32 (reversed) or CRC-32/BZIP2 (normal) with the usual
+    /// `final_xor` of `0xffffffff`; `0` matches implementations which do not invert the result.
+    pub fn new(crc: u32, reversed: bool, refin: bool, refout: bool, final_xor: u32) -> Self {
+        let polynomial = if reversed {
+            CRC_POLYNOMIAL_REVERSED
+        } else {
+            CRC_POLYNOMIAL_NORMAL
+        };
         Crc32Context {
-            crc: crc,
-            reversed: reversed,
-            refin: refin,
-            refout: refout,
+            crc,
+            polynomial,
+            reversed,
+            refin,
+            refout,
+            final_xor,
+        }
+    }
+
+    /// Creates a context for an arbitrary CRC-32 variant.
+    /// # Arguments
+    /// * `crc` - Initial value.
+    /// * `polynomial` - Polynomial in normal notation (e.g. `0x04c11db7`), it is bit-reversed
+    ///   internally when `reversed` is true.
+    /// * `reversed` - Use the reversed (LSB first) algorithm.
+    /// * `refin` - Reflect input bytes (normal algorithm only).
+    /// * `refout` - Reflect the result before the final XOR.
+    /// * `final_xor` - XORed with the result (`0xffffffff` usually).
+    pub fn with_polynomial(crc: u32, polynomial: u32, reversed: bool, refin: bool, refout: bool, final_xor: u32) -> Self {
+        let mut context = Crc32Context::new(crc, reversed, refin, refout, final_xor);
+        if reversed {
+            context.polynomial = polynomial.reverse_bits();
+        } else {
+            context.polynomial = polynomial;
         }
+        context
     }
 
     fn step_normal(&mut self, byte: u8) {
@@ -28,7 +105,7 @@ impl Crc32Context {
 
         for _ in 0..8 {
             if self.crc & 0x80000000 != 0 {
-                self.crc = (self.crc << 1) ^ CRC_POLYNOMIAL_NORMAL;
+                self.crc = (self.crc << 1) ^ self.polynomial;
             } else {
                 self.crc <<= 1;
             }
@@ -39,7 +116,7 @@ impl Crc32Context {
         self.crc ^= byte as u32;
         for _ in 0..8 {
             if self.crc & 1 == 1 {
-                self.crc = (self.crc >> 1) ^ CRC_POLYNOMIAL_REVERSED;
+                self.crc = (self.crc >> 1) ^ self.polynomial;
             } else {
                 self.crc >>= 1;
             }
@@ -54,6 +131,15 @@ impl Crc32Context {
         }
     }
 
+    /// Feeds all the bytes of `bytes`, like calling `step` on each of them.
+    pub fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.step(byte);
+        }
+    }
+
+    /// Returns the checksum of the bytes fed so far. The context is not modified: it can be
+    /// called any number of times, and more bytes can be fed afterwards.
     pub fn finalize(&self) -> u32 {
         let crc: u32;
 
@@ -62,6 +148,6 @@ impl Crc32Context {
         } else {
             crc = self.crc;
         }
-        !crc
+        crc ^ self.final_xor
     }
 }
@@ -0,0 +1,110 @@
+//! Frames encoded at compile time, see `min_frame!`.
+
+use crate::crc::crc32_step;
+
+const HEADER_BYTE: u8 = 0xaa;
+const STUFF_BYTE: u8 = 0x55;
+const EOF_BYTE: u8 = 0x55;
+
+/// Byte `i` of the stuffed part of an application frame: ID, length, payload and checksum.
+const fn body_byte(id: u8, payload: &[u8], crc: u32, i: usize) -> u8 {
+    if i == 0 {
+        id & 0x3f
+    } else if i == 1 {
+        payload.len() as u8
+    } else if i < payload.len() + 2 {
+        payload[i - 2]
+    } else {
+        (crc >> (8 * (payload.len() + 5 - i))) as u8
+    }
+}
+
+/// Checksum of the ID, the length and the payload, like `crc32` of them.
+const fn body_crc(id: u8, payload: &[u8]) -> u32 {
+    let mut crc = crc32_step(0xffffffff, id & 0x3f);
+    crc = crc32_step(crc, payload.len() as u8);
+    let mut i = 0;
+    while i < payload.len() {
+        crc = crc32_step(crc, payload[i]);
+        i += 1;
+    }
+    crc ^ 0xffffffff
+}
+
+/// Returns the number of bytes of the application frame carrying `payload`, stuff bytes
+/// included.
+/// # Panics
+/// Panics (at compile time in a constant) if the payload is longer than 255 bytes.
+pub const fn encoded_len(id: u8, payload: &[u8]) -> usize {
+    assert!(payload.len() <= u8::MAX as usize, "payload longer than 255 bytes");
+    let crc = body_crc(id, payload);
+    // Header bytes and EOF are never stuffed
+    let mut len = 4;
+    let mut header_byte_countdown = 2;
+    let mut i = 0;
+    while i < payload.len() + 6 {
+        len += 1;
+        if body_byte(id, payload, crc, i) == HEADER_BYTE {
+            header_byte_countdown -= 1;
+            if header_byte_countdown == 0 {
+                len += 1;
+                header_byte_countdown = 2;
+            }
+        } else {
+            header_byte_countdown = 2;
+        }
+        i += 1;
+    }
+    len
+}
+
+/// Returns the on-wire bytes of the application frame carrying `payload`, as
+/// `Context::send` would send them with the default checksum. `N` must be
+/// `encoded_len(id, payload)`.
+/// # Panics
+/// Panics (at compile time in a constant) if `N` is not the length of the frame.
+pub const fn encode<const N: usize>(id: u8, payload: &[u8]) -> [u8; N] {
+    assert!(N == encoded_len(id, payload), "wrong frame length");
+    let crc = body_crc(id, payload);
+    let mut frame = [0_u8; N];
+    frame[0] = HEADER_BYTE;
+    frame[1] = HEADER_BYTE;
+    frame[2] = HEADER_BYTE;
+    let mut len = 3;
+    let mut header_byte_countdown = 2;
+    let mut i = 0;
+    while i < payload.len() + 6 {
+        let byte = body_byte(id, payload, crc, i);
+        frame[len] = byte;
+        len += 1;
+        if byte == HEADER_BYTE {
+            header_byte_countdown -= 1;
+            if header_byte_countdown == 0 {
+                frame[len] = STUFF_BYTE;
+                len += 1;
+                header_byte_countdown = 2;
+            }
+        } else {
+            header_byte_countdown = 2;
+        }
+        i += 1;
+    }
+    frame[len] = EOF_BYTE;
+    frame
+}
+
+/// Builds the on-wire bytes of an application frame at compile time, stuff bytes and checksum
+/// included, as an array constant.
+///
+/// ```
+/// const FRAME: [u8; 12] = min_rs::min_frame!(id = 1, payload = [0x01, 0x02]);
+/// assert_eq!(&[0xaa, 0xaa, 0xaa, 0x01, 0x02, 0x01, 0x02][..], &FRAME[..7]);
+/// ```
+#[macro_export]
+macro_rules! min_frame {
+    (id = $id:expr, payload = [$($byte:expr),* $(,)?]) => {{
+        const PAYLOAD: &[u8] = &[$($byte),*];
+        const FRAME: [u8; $crate::frame::encoded_len($id, PAYLOAD)] = $crate::frame::encode($id, PAYLOAD);
+        FRAME
+    }};
+}
@@ -1,6 +1,145 @@
 pub trait Interface {
-    fn tx_space(&self) -> u16;
+    /// Returns the number of bytes that can be written right now without blocking. The default
+    /// is `u16::MAX`, for links that always take a whole frame; see also
+    /// `Context::set_tx_space_override`.
+    fn tx_space(&self) -> u16 {
+        u16::MAX
+    }
     fn tx_start(&self);
     fn tx_finished(&self);
     fn tx_byte(&self, port: u8, byte:u8);
+    /// Returns whether the physical link is up (e.g. carrier or DSR detected). The transport
+    /// protocol does not send or retransmit frames while the link is down.
+    fn link_up(&self) -> bool {
+        true
+    }
+    /// Called when a frame being received is abandoned because of an error (bad checksum,
+    /// length, stuffing or EOF), e.g. to toggle a debug pin.
+    fn on_rx_error(&self) {}
+    /// Returns the tx space when nothing is being sent, `None` if unknown. A frame larger than
+    /// that can never be sent, see `Context::try_send_frame`.
+    fn tx_capacity(&self) -> Option<u16> {
+        None
+    }
+}
+
+/// `Interface` built from callbacks, for users who prefer closures to implementing the trait.
+pub struct FnInterface<S, F, P, B>
+where
+    S: Fn(),
+    F: Fn(),
+    P: Fn() -> u16,
+    B: Fn(u8, u8),
+{
+    tx_start: S,
+    tx_finished: F,
+    tx_space: P,
+    tx_byte: B,
+}
+
+impl<S, F, P, B> FnInterface<S, F, P, B>
+where
+    S: Fn(),
+    F: Fn(),
+    P: Fn() -> u16,
+    B: Fn(u8, u8),
+{
+    /// # Arguments
+    /// * `tx_start` - Callback. Indcates when frame transmission is starting.
+    /// * `tx_finished` - Callback. Indcates when frame transmission is finished.
+    /// * `tx_space` - Callback. Returns current buffer space.
+    /// * `tx_byte` - Callback. Sends a byte on the given port.
+    pub fn new(tx_start: S, tx_finished: F, tx_space: P, tx_byte: B) -> Self {
+        FnInterface {
+            tx_start,
+            tx_finished,
+            tx_space,
+            tx_byte,
+        }
+    }
+}
+
+impl<S, F, P, B> Interface for FnInterface<S, F, P, B>
+where
+    S: Fn(),
+    F: Fn(),
+    P: Fn() -> u16,
+    B: Fn(u8, u8),
+{
+    fn tx_space(&self) -> u16 {
+        (self.tx_space)()
+    }
+
+    fn tx_start(&self) {
+        (self.tx_start)()
+    }
+
+    fn tx_finished(&self) {
+        (self.tx_finished)()
+    }
+
+    fn tx_byte(&self, port: u8, byte: u8) {
+        (self.tx_byte)(port, byte)
+    }
+}
+
+/// `Interface` sending every frame on several interfaces at once, e.g. for a context at the
+/// center of a star topology. The tx space is the smallest one, so that a frame is only sent if
+/// it fits in all of them.
+///
+/// Only the sending side is fanned out: the bytes received from each interface must be fed to
+/// the context (or to separate contexts, see `Multiplexer`) by the caller.
+pub struct BroadcastInterface<'a> {
+    interfaces: Vec<Box<dyn Interface + 'a>>,
+}
+
+impl<'a> BroadcastInterface<'a> {
+    pub fn new(interfaces: Vec<Box<dyn Interface + 'a>>) -> Self {
+        BroadcastInterface {
+            interfaces,
+        }
+    }
+}
+
+impl<'a> Interface for BroadcastInterface<'a> {
+    fn tx_space(&self) -> u16 {
+        self.interfaces.iter().map(|interface| interface.tx_space()).min().unwrap_or(0)
+    }
+
+    fn tx_start(&self) {
+        for interface in &self.interfaces {
+            interface.tx_start();
+        }
+    }
+
+    fn tx_finished(&self) {
+        for interface in &self.interfaces {
+            interface.tx_finished();
+        }
+    }
+
+    fn tx_byte(&self, port: u8, byte: u8) {
+        for interface in &self.interfaces {
+            interface.tx_byte(port, byte);
+        }
+    }
+
+    /// Up as long as one of the links is.
+    fn link_up(&self) -> bool {
+        self.interfaces.iter().any(|interface| interface.link_up())
+    }
+
+    fn on_rx_error(&self) {
+        for interface in &self.interfaces {
+            interface.on_rx_error();
+        }
+    }
+
+    /// The smallest capacity, `None` if one of the interfaces doesn't know its own.
+    fn tx_capacity(&self) -> Option<u16> {
+        if self.interfaces.is_empty() {
+            return None;
+        }
+        self.interfaces.iter().try_fold(u16::MAX, |capacity, interface| interface.tx_capacity().map(|c| c.min(capacity)))
+    }
 }
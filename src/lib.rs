@@ -9,16 +9,14 @@
 //! 
 //! struct Uart {
 //!     name: String,
-//!     tx_space_avaliable: u16,
 //!     sender: Sender<u8>,
 //!     receiver: Receiver<u8>,
 //! }
 //! 
 //! impl Uart {
-//!     fn new(name: String, tx_space_avaliable: u16, sender: Sender<u8>, receiver: Receiver<u8>) -> Self {
+//!     fn new(name: String, sender: Sender<u8>, receiver: Receiver<u8>) -> Self {
 //!         Uart{
 //!             name: name,
-//!             tx_space_avaliable: tx_space_avaliable,
 //!             sender: sender,
 //!             receiver: receiver,
 //!         }
@@ -32,10 +30,6 @@
 //!         println!("{}: Close uart.", self.name);
 //!     }
 //! 
-//!     fn available_for_write(&self) -> u16 {
-//!         self.tx_space_avaliable
-//!     }
-//! 
 //!     fn tx(&self, byte: u8) {
 //!         print!("0x{:02x} ", byte);
 //!         match self.sender.send(byte) {
@@ -55,9 +49,6 @@
 //!     fn tx_finished(&self) {
 //!         println!("]");
 //!     }
-//!     fn tx_space(&self) -> u16 {
-//!         self.available_for_write()
-//!     }
 //!     
 //!     fn tx_byte(&self, _port: u8, byte: u8) {
 //!         self.tx(byte);
@@ -72,7 +63,7 @@
 //!     
 //!     let app1_builder = thread::Builder::new().name("app1".into());
 //!     let app1 = app1_builder.spawn(move || {
-//!         let uart1 = Uart::new(String::from("uart1"), 128, tx1, rx1);
+//!         let uart1 = Uart::new(String::from("uart1"), tx1, rx1);
 //!         let mut min1 = min::Context::new(
 //!             String::from("min1"),
 //!             &uart1,
@@ -89,7 +80,8 @@
 //! 
 //!         match min1.send_frame(id, &tx_data, tx_data.len() as u8) {
 //!             Ok(size) => {
-//!                 println!("{}: {} bytes sent.", min1.hw_if.name, size);
+//!                 // The name of the context (log target) and the one of its interface are distinct
+//!                 println!("{} on {}: {} bytes sent.", min1.name(), min1.hw_if.name, size);
 //!             },
 //!             Err(_) => panic!("Opps!"),
 //!         };
@@ -99,7 +91,7 @@
 //!     
 //!     let app2_builder = thread::Builder::new().name("app2".into());
 //!     let app2 = app2_builder.spawn(move || {
-//!         let uart2 = Uart::new(String::from("uart2"), 128, tx2, rx2);
+//!         let uart2 = Uart::new(String::from("uart2"), tx2, rx2);
 //!         let mut min2 = min::Context::new(
 //!             String::from("min2"),
 //!             &uart2,
@@ -132,11 +124,50 @@
 //! }
 //! ```
 
+//! ## Features
+//! * `std` (default) - The whole MIN library.
+//! * `crc` - Only the CRC-32 (`Crc32Context`, `crc32`) and the frames built at compile time
+//!   (`min_frame!`), usable in `no_std` projects with `default-features = false`.
+//! * `serde` - `Serialize`/`Deserialize` for messages and statistics.
+//! * `test-util` - Helpers for testing code using MIN (`test_util::RecordingInterface`,
+//!   `test_util::assert_frame_eq`, `Context::inject_frame`).
+//! * `debug-checks` - Panics when the invariants of the transport window and FIFO are broken,
+//!   for development.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(any(feature = "std", feature = "crc"))]
 mod crc;
+#[cfg(feature = "std")]
 mod transport;
 
+#[cfg(any(feature = "std", feature = "crc"))]
+pub mod frame;
+
+#[cfg(feature = "std")]
+pub mod clock;
+#[cfg(feature = "std")]
 pub mod context;
+#[cfg(feature = "std")]
 pub mod interface;
+#[cfg(feature = "std")]
+pub mod multiplexer;
+#[cfg(feature = "std")]
+pub mod rng;
+#[cfg(feature = "test-util")]
+pub mod test_util;
 
+#[cfg(feature = "std")]
+pub use clock::*;
+#[cfg(feature = "std")]
 pub use context::*;
+#[cfg(feature = "std")]
 pub use interface::*;
+#[cfg(feature = "std")]
+pub use multiplexer::*;
+#[cfg(feature = "std")]
+pub use rng::*;
+#[cfg(feature = "std")]
+pub use transport::{seq_distance, seq_in_window, seq_lt, ConfigError, TransportConfig, TransportState, TransportStats};
+#[cfg(any(feature = "std", feature = "crc"))]
+pub use crc::{crc32, Crc32Context};
@@ -0,0 +1,108 @@
+use crate::context::Context;
+use std::collections::HashMap;
+
+const HEADER_BYTE: u8 = 0xaa;
+
+/// Dispatches received bytes to the MIN contexts registered for the port they arrived on.
+pub struct Multiplexer<'a, T> where T: crate::Interface {
+    contexts: HashMap<u8, Context<'a, T>>,
+    /// Header bytes seen in a row on each port without context
+    unrouted_header_bytes: HashMap<u8, u8>,
+    /// Number of frames received on ports without context
+    unrouted_frames: u32,
+    /// Index (in port order) of the context ticked first on the next `tick`
+    next_turn: usize,
+    /// Number of transport frames sent by each context in `tick`
+    frames_sent: HashMap<u8, u32>,
+}
+
+impl<'a, T> Multiplexer<'a, T> where T: crate::Interface {
+    pub fn new() -> Self {
+        Multiplexer {
+            contexts: HashMap::new(),
+            unrouted_header_bytes: HashMap::new(),
+            unrouted_frames: 0,
+            next_turn: 0,
+            frames_sent: HashMap::new(),
+        }
+    }
+
+    /// Registers `context` for `port`, returns the context previously registered for it, if any.
+    pub fn add_context(&mut self, port: u8, context: Context<'a, T>) -> Option<Context<'a, T>> {
+        self.unrouted_header_bytes.remove(&port);
+        self.contexts.insert(port, context)
+    }
+
+    /// Unregisters the context of `port` and returns it.
+    pub fn remove_context(&mut self, port: u8) -> Option<Context<'a, T>> {
+        self.contexts.remove(&port)
+    }
+
+    pub fn get(&self, port: u8) -> Option<&Context<'a, T>> {
+        self.contexts.get(&port)
+    }
+
+    pub fn get_mut(&mut self, port: u8) -> Option<&mut Context<'a, T>> {
+        self.contexts.get_mut(&port)
+    }
+
+    /// Feeds bytes received on `port` into its context. Bytes for a port without context are
+    /// dropped, the frames they start are counted.
+    pub fn receive(&mut self, port: u8, buf: &[u8]) {
+        if let Some(context) = self.contexts.get_mut(&port) {
+            context.receive(buf);
+        } else {
+            let header_bytes = self.unrouted_header_bytes.entry(port).or_insert(0);
+            for &byte in buf {
+                if byte == HEADER_BYTE {
+                    *header_bytes += 1;
+                    if *header_bytes == 3 {
+                        // Start of frame
+                        self.unrouted_frames = self.unrouted_frames.wrapping_add(1);
+                        *header_bytes = 0;
+                    }
+                } else {
+                    *header_bytes = 0;
+                }
+            }
+        }
+    }
+
+    /// Runs the transport timeouts of all the contexts, each sending up to one new frame.
+    ///
+    /// The contexts take turns at going first, so that a chatty context can't take all the tx
+    /// space of a shared interface cycle after cycle.
+    pub fn tick(&mut self) {
+        let mut ports: Vec<u8> = self.contexts.keys().copied().collect();
+        if ports.is_empty() {
+            return;
+        }
+        ports.sort_unstable();
+        let first = self.next_turn % ports.len();
+        self.next_turn = (first + 1) % ports.len();
+        for i in 0..ports.len() {
+            let port = ports[(first + i) % ports.len()];
+            if let Some(context) = self.contexts.get_mut(&port) {
+                let report = context.tick();
+                let sent = self.frames_sent.entry(port).or_insert(0);
+                *sent = sent.wrapping_add((report.frames_sent + report.frames_retransmitted) as u32);
+            }
+        }
+    }
+
+    /// Number of transport frames (new or retransmitted) the context of `port` sent in `tick`.
+    pub fn sent_count(&self, port: u8) -> u32 {
+        self.frames_sent.get(&port).copied().unwrap_or(0)
+    }
+
+    /// Number of frames received on ports without context.
+    pub fn unrouted_count(&self) -> u32 {
+        self.unrouted_frames
+    }
+}
+
+impl<'a, T> Default for Multiplexer<'a, T> where T: crate::Interface {
+    fn default() -> Self {
+        Multiplexer::new()
+    }
+}
@@ -0,0 +1,30 @@
+/// Source of pseudo-random numbers for the retransmit jitter, see `Context::set_rng`.
+///
+/// Only needs `core`: wrap a hardware RNG on embedded targets, or a real RNG under `std`.
+pub trait Rng {
+    /// Returns the next pseudo-random number.
+    fn next_u32(&mut self) -> u32;
+}
+
+/// xorshift32 generator, the default `Rng`: the same seed gives the same sequence.
+pub struct XorShift32 {
+    state: u32,
+}
+
+impl XorShift32 {
+    pub fn new(seed: u32) -> Self {
+        XorShift32 {
+            // xorshift is stuck at 0
+            state: if seed == 0 { 1 } else { seed },
+        }
+    }
+}
+
+impl Rng for XorShift32 {
+    fn next_u32(&mut self) -> u32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state
+    }
+}
@@ -0,0 +1,126 @@
+use crate::context::{Context, FrameError};
+use crate::interface::Interface;
+use std::cell::{Cell, RefCell};
+
+/// `Interface` recording the bytes of the last frame sent, to assert the exact wire output
+/// of a `Context` in tests.
+pub struct RecordingInterface {
+    tx_space: Cell<u16>,
+    sent: RefCell<Vec<u8>>,
+}
+
+impl RecordingInterface {
+    /// Construct a `RecordingInterface` reporting `tx_space` bytes of space in its tx buffer.
+    pub fn new(tx_space: u16) -> Self {
+        RecordingInterface {
+            tx_space: Cell::new(tx_space),
+            sent: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Changes the space reported in the tx buffer.
+    pub fn set_tx_space(&self, tx_space: u16) {
+        self.tx_space.set(tx_space);
+    }
+
+    /// Returns the bytes sent since the start of the last frame.
+    pub fn sent_bytes(&self) -> Vec<u8> {
+        self.sent.borrow().clone()
+    }
+}
+
+impl Interface for RecordingInterface {
+    fn tx_space(&self) -> u16 {
+        self.tx_space.get()
+    }
+
+    fn tx_start(&self) {
+        self.sent.borrow_mut().clear();
+    }
+
+    fn tx_finished(&self) {}
+
+    fn tx_byte(&self, _port: u8, byte: u8) {
+        self.sent.borrow_mut().push(byte);
+    }
+}
+
+/// Fields of a frame as parsed by the receiving state machine of a `Context`, see
+/// `Context::parse_frame`. The fields the parsing didn't reach are `None` or short.
+#[derive(Default, PartialEq)]
+pub(crate) struct FrameFields {
+    /// A frame start (the header) was found
+    pub(crate) started: bool,
+    pub(crate) id_control: Option<u8>,
+    pub(crate) seq: Option<u8>,
+    pub(crate) length: Option<u8>,
+    pub(crate) payload: Vec<u8>,
+    /// Checksum as received, checked or not
+    pub(crate) checksum: Option<u32>,
+    pub(crate) eof: Option<u8>,
+    /// Error which dropped the frame
+    pub(crate) error: Option<FrameError>,
+}
+
+/// Parses the on-wire bytes of a frame with a transport `Context`, so that the sequence numbers
+/// are parsed too.
+fn parse_frame(bytes: &[u8]) -> FrameFields {
+    let uart = RecordingInterface::new(u16::MAX);
+    let mut min = Context::new(String::from("assert_frame_eq"), &uart, 0, true);
+    min.parse_frame(bytes)
+}
+
+/// Returns the first field which differs between two frames, described for a test failure.
+fn frame_diff(produced: &[u8], expected: &[u8]) -> Option<String> {
+    let produced = parse_frame(produced);
+    let expected = parse_frame(expected);
+    if produced.started != expected.started {
+        return Some(format!("header: frame start found in produced {}, in expected {}", produced.started, expected.started));
+    }
+    if produced.id_control != expected.id_control {
+        return Some(format!("ID/control: produced {:02x?}, expected {:02x?}", produced.id_control, expected.id_control));
+    }
+    if produced.seq != expected.seq {
+        return Some(format!("sequence number: produced {:02x?}, expected {:02x?}", produced.seq, expected.seq));
+    }
+    if produced.length != expected.length {
+        return Some(format!("length: produced {:02x?}, expected {:02x?}", produced.length, expected.length));
+    }
+    for i in 0..produced.payload.len().max(expected.payload.len()) {
+        if produced.payload.get(i) != expected.payload.get(i) {
+            return Some(format!("payload byte {}: produced {:02x?}, expected {:02x?}", i, produced.payload.get(i), expected.payload.get(i)));
+        }
+    }
+    // Most significant byte first, as sent
+    for i in 0..4 {
+        let produced_byte = produced.checksum.map(|checksum| checksum.to_be_bytes()[i]);
+        let expected_byte = expected.checksum.map(|checksum| checksum.to_be_bytes()[i]);
+        if produced_byte != expected_byte {
+            return Some(format!("CRC byte {}: produced {:02x?}, expected {:02x?}", i, produced_byte, expected_byte));
+        }
+    }
+    if produced.eof != expected.eof {
+        return Some(format!("EOF: produced {:02x?}, expected {:02x?}", produced.eof, expected.eof));
+    }
+    if produced.error != expected.error {
+        return Some(format!("receive error: produced {:?}, expected {:?}", produced.error, expected.error));
+    }
+    None
+}
+
+/// Asserts that two frames, as sent on the wire, are equal. On mismatch the panic message
+/// names the first field which differs (header, ID/control, sequence number, length, payload
+/// byte, CRC byte, EOF) rather than a raw byte offset, which the stuff bytes would shift.
+///
+/// Both frames are parsed by the receiving state machine of a `Context` with the default
+/// framing: frames carrying a piggybacked Rn or a header checksum are not supported.
+pub fn assert_frame_eq(produced: &[u8], expected: &[u8]) {
+    if produced == expected {
+        return;
+    }
+    match frame_diff(produced, expected) {
+        Some(diff) => panic!("frames differ at {}\n  produced: {:02x?}\n  expected: {:02x?}", diff, produced, expected),
+        // Same fields, the stuffing or the bytes around the frame differ
+        None => panic!("frames differ in their stuff bytes or outside the frame\n  produced: {:02x?}\n  expected: {:02x?}", produced, expected),
+    }
+}
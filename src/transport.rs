@@ -1,5 +1,6 @@
+use crate::clock::{Clock, SystemClock};
 use std::collections::VecDeque;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::fmt;
 
 pub const TRANSPORT_MAX_PAYLOAD_LEN: u8 = u8::MAX;
 pub const TRANSPORT_FIFO_MAX_FRAMES: u8 = 31;
@@ -12,6 +13,22 @@ pub const TRANSPORT_FRAME_RETRANSMIT_TIMEOUT_MS: u128 = 1000;
 pub const ACK: u8 = 0xff;
 pub const RESET: u8 = 0xfe;
 
+/// Number of steps from `from` to `to` going forward on the ring of the 8-bit sequence numbers.
+pub fn seq_distance(from: u8, to: u8) -> u8 {
+    to.wrapping_sub(from)
+}
+
+/// Whether the sequence number `a` comes before `b`, that is `b` is less than half the ring
+/// ahead of `a`.
+pub fn seq_lt(a: u8, b: u8) -> bool {
+    (b.wrapping_sub(a) as i8) > 0
+}
+
+/// Whether the sequence number `seq` lies in `min..=max` on the ring.
+pub fn seq_in_window(seq: u8, min: u8, max: u8) -> bool {
+    seq_distance(min, seq) <= seq_distance(min, max)
+}
+
 #[derive(Copy, Clone)]
 pub struct TransportFrame {
     /// When frame was last sent (used for re-send timeouts)
@@ -23,6 +40,12 @@ pub struct TransportFrame {
     pub min_id: u8,
     /// Sequence number of frame
     pub seq: u8,
+    /// Token of the ping carried by the frame, see `Context::ping`
+    pub ping: Option<u32>,
+    /// Number of times the frame was sent again
+    pub retransmits: u8,
+    /// How long to wait for the ACK since the frame was last sent before sending it again
+    pub retransmit_timeout_ms: u128,
 }
 
 impl TransportFrame {
@@ -33,6 +56,9 @@ impl TransportFrame {
             payload_len: len,
             min_id: min_id,
             seq: 0,
+            ping: None,
+            retransmits: 0,
+            retransmit_timeout_ms: TRANSPORT_FRAME_RETRANSMIT_TIMEOUT_MS,
         };
         for i in 0..len as usize {
             frame.payload[i] = payload[i];
@@ -41,6 +67,151 @@ impl TransportFrame {
     }
 }
 
+/// Sequence numbers of the transport protocol, used to resume a session after a restart.
+///
+/// Only the sequence numbers are kept: the payloads of queued and in-flight frames are not
+/// part of the state and are lost. Restoring is only safe if the window was empty
+/// (`sn_min == sn_max`) when the state was taken; otherwise the other side keeps waiting for
+/// frames that will never be retransmitted, and the transport must be reset instead. When
+/// restored, the window is emptied: the next frame sent gets `sn_min`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TransportState {
+    /// Oldest unacknowledged sequence number
+    pub sn_min: u8,
+    /// Next sequence number to be sent
+    pub sn_max: u8,
+    /// Next sequence number expected from the other side
+    pub rn: u8,
+}
+
+impl TransportState {
+    /// Size of the serialized state in bytes.
+    pub const SERIALIZED_LEN: usize = 3;
+
+    /// Serializes the state as `[sn_min, sn_max, rn]`.
+    pub fn to_bytes(&self) -> [u8; TransportState::SERIALIZED_LEN] {
+        [self.sn_min, self.sn_max, self.rn]
+    }
+
+    /// Deserializes a state produced by `to_bytes`, returns `None` if `buf` is too short.
+    pub fn from_bytes(buf: &[u8]) -> Option<Self> {
+        if buf.len() < TransportState::SERIALIZED_LEN {
+            return None;
+        }
+        Some(TransportState {
+            sn_min: buf[0],
+            sn_max: buf[1],
+            rn: buf[2],
+        })
+    }
+}
+
+/// Limits and timeouts of the transport protocol, see `Context::with_transport_config`.
+///
+/// The limits can only be lowered from the defaults: the window and FIFO sizes are bounded by
+/// `TRANSPORT_MAX_WINDOW_SIZE` and `TRANSPORT_FIFO_MAX_FRAMES`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TransportConfig {
+    /// Largest number of frames in flight (sent and not acknowledged yet)
+    pub window_size: u8,
+    /// Largest number of frames in the FIFO, in flight ones included
+    pub fifo_capacity: u8,
+    /// How often an ACK is sent again while the other side is active
+    pub ack_timeout_ms: u128,
+    /// How long a frame waits for its ACK before being sent again
+    pub retransmit_timeout_ms: u128,
+    /// How long without hearing from the other side before it is considered gone
+    pub idle_timeout_ms: u128,
+}
+
+impl Default for TransportConfig {
+    fn default() -> Self {
+        TransportConfig {
+            window_size: TRANSPORT_MAX_WINDOW_SIZE,
+            fifo_capacity: TRANSPORT_FIFO_MAX_FRAMES,
+            ack_timeout_ms: TRANSPORT_ACK_RETRANSMIT_TIMEOUT_MS,
+            retransmit_timeout_ms: TRANSPORT_FRAME_RETRANSMIT_TIMEOUT_MS,
+            idle_timeout_ms: TRANSPORT_IDLE_TIMEOUT_MS,
+        }
+    }
+}
+
+impl TransportConfig {
+    /// Checks that the limits are supported and consistent with each other.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.window_size == 0 || self.window_size > TRANSPORT_MAX_WINDOW_SIZE {
+            return Err(ConfigError::WindowSize(self.window_size));
+        }
+        if self.fifo_capacity == 0 || self.fifo_capacity > TRANSPORT_FIFO_MAX_FRAMES {
+            return Err(ConfigError::FifoCapacity(self.fifo_capacity));
+        }
+        if self.window_size > self.fifo_capacity {
+            return Err(ConfigError::WindowOverFifo(self.window_size, self.fifo_capacity));
+        }
+        if self.ack_timeout_ms >= self.retransmit_timeout_ms {
+            return Err(ConfigError::AckTimeoutOverRetransmit(self.ack_timeout_ms, self.retransmit_timeout_ms));
+        }
+        if self.idle_timeout_ms == 0 {
+            return Err(ConfigError::IdleTimeout);
+        }
+        Ok(())
+    }
+}
+
+/// Inconsistency found by `TransportConfig::validate`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ConfigError {
+    /// The window size is 0 or over `TRANSPORT_MAX_WINDOW_SIZE`.
+    WindowSize(u8),
+    /// The FIFO capacity is 0 or over `TRANSPORT_FIFO_MAX_FRAMES`.
+    FifoCapacity(u8),
+    /// The window is larger than the FIFO holding its frames. The values are the window size and
+    /// the FIFO capacity.
+    WindowOverFifo(u8, u8),
+    /// ACKs would not be resent before the frames they acknowledge, causing spurious
+    /// retransmissions. The values are the ACK and retransmit timeouts.
+    AckTimeoutOverRetransmit(u128, u128),
+    /// The other side would always be considered gone.
+    IdleTimeout,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::WindowSize(size) =>
+                write!(f, "window size {} not in 1..={}", size, TRANSPORT_MAX_WINDOW_SIZE),
+            ConfigError::FifoCapacity(capacity) =>
+                write!(f, "FIFO capacity {} not in 1..={}", capacity, TRANSPORT_FIFO_MAX_FRAMES),
+            ConfigError::WindowOverFifo(size, capacity) =>
+                write!(f, "window size {} over the FIFO capacity {}", size, capacity),
+            ConfigError::AckTimeoutOverRetransmit(ack, retransmit) =>
+                write!(f, "ACK timeout {} ms not below the retransmit timeout {} ms", ack, retransmit),
+            ConfigError::IdleTimeout =>
+                write!(f, "idle timeout is 0 ms"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Statistics of the transport protocol.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TransportStats {
+    /// Number of ACKs received for frames not in the window
+    pub spurious_acks: u32,
+    /// Number of frames dropped because of an unexpected sequence number
+    pub sequence_mismatch_drop: u32,
+    /// Number of RESETs received
+    pub resets_received: u32,
+    /// Larger number of frames in the FIFO
+    pub n_frames_max: u8,
+    /// Number of frames sent again because their ACK didn't come in time
+    pub retransmits_timeout: u32,
+    /// Number of frames sent again because the other side asked for them (NACK)
+    pub retransmits_nack: u32,
+}
+
 pub struct Transport {
     pub frames: VecDeque<TransportFrame>,
     pub last_sent_ack_time_ms: u128,
@@ -49,6 +220,8 @@ pub struct Transport {
     pub spurious_acks: u32,
     pub sequence_mismatch_drop: u32,
     pub resets_received: u32,
+    pub retransmits_timeout: u32,
+    pub retransmits_nack: u32,
     /// Number of frames in the FIFO
     pub n_frames: u8,
     /// Larger number of frames in the FIFO
@@ -57,46 +230,99 @@ pub struct Transport {
     pub sn_min: u8,
     pub sn_max: u8,
     pub rn: u8,
+    /// An in-sequence frame or an ACK came from the other side since the last reset
+    pub synchronized: bool,
 }
 
-impl Transport {
-    pub fn new() -> Self {
-        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(std::time::Duration::from_secs(0)).as_millis();
+impl Default for Transport {
+    /// Empty transport with the timers zeroed, see `with_clock` to start them from a clock.
+    fn default() -> Self {
         Transport{
             frames: VecDeque::with_capacity(TRANSPORT_FIFO_MAX_FRAMES as usize),
-            last_sent_ack_time_ms: now,
-            last_received_anything_ms: now,
+            last_sent_ack_time_ms: 0,
+            last_received_anything_ms: 0,
             last_received_frame_ms: 0,
             spurious_acks: 0,
             sequence_mismatch_drop: 0,
             resets_received: 0,
+            retransmits_timeout: 0,
+            retransmits_nack: 0,
             n_frames: 0,
             n_frames_max: 0,
             sn_min: 0,
             sn_max: 0,
             rn: 0,
+            synchronized: false,
         }
     }
+}
 
-    pub fn reset_transport_fifo(&mut self) {
-        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(std::time::Duration::from_secs(0)).as_millis();
+impl Transport {
+    pub fn new() -> Self {
+        Transport::with_clock(&SystemClock)
+    }
 
+    /// Empty transport with the timers started from the current time of `clock`.
+    pub fn with_clock(clock: &impl Clock) -> Self {
+        let mut transport = Transport::default();
+        transport.restart_timers(clock.now_ms());
+        transport
+    }
+
+    /// Returns the number of frames that were in the FIFO before clearing.
+    pub fn reset_transport_fifo(&mut self, now: u128) -> usize {
+        let discarded = self.frames.len();
         // Clear down the transmission FIFO queue
         self.frames.clear();
         self.n_frames = 0;
         self.sn_max = 0;
         self.sn_min = 0;
         self.rn = 0;
+        self.synchronized = false;
 
         // Reset the timers
+        self.restart_timers(now);
+
+        discarded
+    }
+
+    pub fn restart_timers(&mut self, now: u128) {
         self.last_received_anything_ms = now;
         self.last_sent_ack_time_ms = now;
         self.last_received_frame_ms = 0;
     }
 
     pub fn pop(&mut self) {
-        self.frames.pop_front();
-        self.n_frames -= 1;
+        if self.frames.pop_front().is_some() {
+            self.n_frames -= 1;
+        }
+    }
+
+    pub fn get_stats(&self) -> TransportStats {
+        TransportStats {
+            spurious_acks: self.spurious_acks,
+            sequence_mismatch_drop: self.sequence_mismatch_drop,
+            resets_received: self.resets_received,
+            n_frames_max: self.n_frames_max,
+            retransmits_timeout: self.retransmits_timeout,
+            retransmits_nack: self.retransmits_nack,
+        }
+    }
+
+    pub fn get_state(&self) -> TransportState {
+        TransportState {
+            sn_min: self.sn_min,
+            sn_max: self.sn_max,
+            rn: self.rn,
+        }
+    }
+
+    /// Restores the sequence numbers with an empty window (`sn_max` rewound to `sn_min`): the FIFO
+    /// is empty, there is no frame in flight.
+    pub fn set_state(&mut self, state: TransportState) {
+        self.sn_min = state.sn_min;
+        self.sn_max = state.sn_min;
+        self.rn = state.rn;
     }
 
     pub fn get_drop_cnt(&self) -> u32 {
@@ -110,4 +336,39 @@ impl Transport {
     pub fn get_spurious_ack_cnt(&self) -> u32 {
         self.spurious_acks
     }
+
+    pub fn get_retransmit_timeout_cnt(&self) -> u32 {
+        self.retransmits_timeout
+    }
+
+    pub fn get_retransmit_nack_cnt(&self) -> u32 {
+        self.retransmits_nack
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockClock;
+
+    // `Transport` is not exported, so its constructors are tested here
+    #[test]
+    fn transport_default() {
+        let transport = Transport::default();
+        assert_eq!(0, transport.last_received_anything_ms);
+        assert_eq!(0, transport.last_sent_ack_time_ms);
+        assert_eq!(0, transport.last_received_frame_ms);
+        assert_eq!(TransportState { sn_min: 0, sn_max: 0, rn: 0 }, transport.get_state());
+        assert!(transport.frames.is_empty());
+    }
+
+    #[test]
+    fn transport_with_clock() {
+        let clock = MockClock::new(1234);
+        let transport = Transport::with_clock(&clock);
+        assert_eq!(1234, transport.last_received_anything_ms);
+        assert_eq!(1234, transport.last_sent_ack_time_ms);
+        assert_eq!(0, transport.last_received_frame_ms);
+        assert!(transport.frames.is_empty());
+    }
 }
@@ -0,0 +1,115 @@
+// Only depends on the `crc` feature, also run in isolation by the `no-std-crc` CI job:
+// cargo test --no-default-features --features crc --tests
+
+#[cfg(test)]
+mod tests {
+    extern crate min_rs as min;
+
+    const CHECK_DATA: &[u8] = b"123456789";
+
+    // Bit-by-bit CRC-32 in normal notation, reflecting input and output when `reflect` is set.
+    fn reference_crc32(data: &[u8], polynomial: u32, init: u32, reflect: bool, xorout: u32) -> u32 {
+        let mut crc = init;
+        for &byte in data {
+            let byte = if reflect { byte.reverse_bits() } else { byte };
+            crc ^= (byte as u32) << 24;
+            for _ in 0..8 {
+                if crc & 0x80000000 != 0 {
+                    crc = (crc << 1) ^ polynomial;
+                } else {
+                    crc <<= 1;
+                }
+            }
+        }
+        if reflect {
+            crc = crc.reverse_bits();
+        }
+        crc ^ xorout
+    }
+
+    fn checksum(mut crc: min::Crc32Context, data: &[u8]) -> u32 {
+        for &byte in data {
+            crc.step(byte);
+        }
+        crc.finalize()
+    }
+
+    #[test]
+    fn default_polynomial() {
+        // CRC-32
+        let crc = min::Crc32Context::new(0xffffffff, true, false, false, 0xffffffff);
+        assert_eq!(0xcbf43926, checksum(crc, CHECK_DATA));
+        // CRC-32/BZIP2
+        let crc = min::Crc32Context::new(0xffffffff, false, false, false, 0xffffffff);
+        assert_eq!(0xfc891918, checksum(crc, CHECK_DATA));
+    }
+
+    #[test]
+    fn custom_polynomial() {
+        let data: [u8; 12] = [0xaa, 0xaa, 0x55, 0xaa, 0x00, 0x01, 0x02, 0x03, 0x80, 0xfe, 0xff, 0x7f];
+
+        // CRC-32C (Castagnoli)
+        let crc = min::Crc32Context::with_polynomial(0xffffffff, 0x1edc6f41, true, false, false, 0xffffffff);
+        assert_eq!(0xe3069283, checksum(crc, CHECK_DATA));
+        let crc = min::Crc32Context::with_polynomial(0xffffffff, 0x1edc6f41, true, false, false, 0xffffffff);
+        assert_eq!(reference_crc32(&data, 0x1edc6f41, 0xffffffff, true, 0xffffffff), checksum(crc, &data));
+
+        // CRC-32Q polynomial with the normal algorithm
+        let crc = min::Crc32Context::with_polynomial(0, 0x814141ab, false, false, false, 0xffffffff);
+        assert_eq!(!0x3010bf7f, checksum(crc, CHECK_DATA));
+        let crc = min::Crc32Context::with_polynomial(0, 0x814141ab, false, false, false, 0xffffffff);
+        assert_eq!(reference_crc32(&data, 0x814141ab, 0, false, 0xffffffff), checksum(crc, &data));
+    }
+
+    #[test]
+    fn chunked_update() {
+        let data: [u8; 12] = [0xaa, 0xaa, 0x55, 0xaa, 0x00, 0x01, 0x02, 0x03, 0x80, 0xfe, 0xff, 0x7f];
+
+        let mut one_shot = min::Crc32Context::new(0xffffffff, true, false, false, 0xffffffff);
+        one_shot.update(&data);
+
+        let mut chunked = min::Crc32Context::new(0xffffffff, true, false, false, 0xffffffff);
+        chunked.update(&data[0..5]);
+        let partial = chunked.finalize();
+        assert_eq!(partial, chunked.finalize());
+        chunked.update(&[]);
+        chunked.update(&data[5..6]);
+        chunked.update(&data[6..]);
+
+        assert_eq!(one_shot.finalize(), chunked.finalize());
+        let crc = min::Crc32Context::new(0xffffffff, true, false, false, 0xffffffff);
+        assert_eq!(checksum(crc, &data), chunked.finalize());
+    }
+
+    #[test]
+    fn final_xor() {
+        let inverted = min::Crc32Context::new(0xffffffff, true, false, false, 0xffffffff);
+        assert_eq!(0xcbf43926, checksum(inverted, CHECK_DATA));
+        let not_inverted = min::Crc32Context::new(0xffffffff, true, false, false, 0);
+        assert_eq!(!0xcbf43926, checksum(not_inverted, CHECK_DATA));
+
+        // CRC-32/JAMCRC
+        let crc = min::Crc32Context::with_polynomial(0xffffffff, 0x04c11db7, true, false, false, 0);
+        assert_eq!(0x340bc6d9, checksum(crc, CHECK_DATA));
+    }
+
+    #[test]
+    fn const_crc32() {
+        const CHECK: u32 = min::crc32(CHECK_DATA);
+        assert_eq!(0xcbf43926, CHECK);
+        let crc = min::Crc32Context::new(0xffffffff, true, false, false, 0xffffffff);
+        assert_eq!(checksum(crc, &[0xaa, 0x55, 0x00, 0xff]), min::crc32(&[0xaa, 0x55, 0x00, 0xff]));
+    }
+
+    #[test]
+    fn const_frame() {
+        const FRAME: [u8; 12] = min::min_frame!(id = 0x41, payload = [0x01, 0x02]);
+        let crc = min::crc32(&[0x01, 0x02, 0x01, 0x02]).to_be_bytes();
+        assert_eq!([0xaa, 0xaa, 0xaa, 0x01, 0x02, 0x01, 0x02, crc[0], crc[1], crc[2], crc[3], 0x55], FRAME);
+
+        // Stuff bytes after every two 0xaa
+        const STUFFED: [u8; 17] = min::min_frame!(id = 0, payload = [0xaa, 0xaa, 0xaa, 0xaa, 0xaa]);
+        assert_eq!([0xaa, 0xaa, 0x55, 0xaa, 0xaa, 0x55, 0xaa], STUFFED[5..12]);
+        assert_eq!(10, min::min_frame!(id = 0, payload = []).len());
+    }
+}
@@ -0,0 +1,113 @@
+#![cfg(feature = "std")]
+
+#[cfg(test)]
+mod tests {
+    extern crate min_rs as min;
+    use std::cell::{Cell, RefCell};
+
+    struct Uart {
+        tx_buf: RefCell<Vec<u8>>,
+    }
+
+    impl Uart {
+        fn new() -> Self {
+            Uart {
+                tx_buf: RefCell::new(Vec::new()),
+            }
+        }
+
+        fn sent(&self) -> Vec<u8> {
+            self.tx_buf.borrow().clone()
+        }
+    }
+
+    impl min::Interface for Uart {
+        fn tx_start(&self) {
+            self.tx_buf.borrow_mut().clear();
+        }
+        fn tx_finished(&self) {}
+        fn tx_space(&self) -> u16 {
+            128
+        }
+        fn tx_byte(&self, _port: u8, byte: u8) {
+            self.tx_buf.borrow_mut().push(byte);
+        }
+    }
+
+    // Interface shared by several contexts, with room for a given number of bytes
+    struct SharedUart {
+        tx_space: Cell<u16>,
+    }
+
+    impl min::Interface for SharedUart {
+        fn tx_start(&self) {}
+        fn tx_finished(&self) {}
+        fn tx_space(&self) -> u16 {
+            self.tx_space.get()
+        }
+        fn tx_byte(&self, _port: u8, _byte: u8) {
+            self.tx_space.set(self.tx_space.get().saturating_sub(1));
+        }
+    }
+
+    #[test]
+    fn route_by_port() {
+        let uart = Uart::new();
+        let uart1 = Uart::new();
+        let uart2 = Uart::new();
+        let mut sender = min::Context::new(String::from("sender"), &uart, 0, false);
+        let mut mux = min::Multiplexer::new();
+        mux.add_context(1, min::Context::new(String::from("min1"), &uart1, 1, false));
+        mux.add_context(2, min::Context::new(String::from("min2"), &uart2, 2, false));
+
+        sender.send_frame(0, &[0x01], 1).unwrap_or(0);
+        mux.receive(1, &uart.sent());
+        sender.send_frame(0, &[0x02], 1).unwrap_or(0);
+        mux.receive(2, &uart.sent());
+        sender.send_frame(0, &[0x03], 1).unwrap_or(0);
+        mux.receive(3, &uart.sent());
+        mux.tick();
+
+        let msg = mux.get_mut(1).unwrap().get_msg().ok().unwrap();
+        assert_eq!((1, vec![0x01]), (msg.port, msg.buf));
+        let msg = mux.get_mut(2).unwrap().get_msg().ok().unwrap();
+        assert_eq!((2, vec![0x02]), (msg.port, msg.buf));
+        assert_eq!(1, mux.unrouted_count());
+
+        assert!(mux.remove_context(2).is_some());
+        mux.receive(2, &uart.sent());
+        assert_eq!(2, mux.unrouted_count());
+    }
+
+    #[test]
+    fn tick_fairness() {
+        let uart = SharedUart {
+            tx_space: Cell::new(0),
+        };
+        // Frozen time, no retransmission
+        let clock = min::MockClock::new(0);
+        let mut flood = min::Context::new(String::from("flood"), &uart, 1, true);
+        flood.set_clock(&clock);
+        let mut quiet = min::Context::new(String::from("quiet"), &uart, 2, true);
+        quiet.set_clock(&clock);
+        let mut mux = min::Multiplexer::new();
+        mux.add_context(1, flood);
+        mux.add_context(2, quiet);
+        for i in 0..10 {
+            mux.get_mut(1).unwrap().queue_frame(0, &[i], 1).unwrap();
+        }
+        for i in 0..4 {
+            mux.get_mut(2).unwrap().queue_frame(0, &[i], 1).unwrap();
+        }
+
+        // Room for a single frame per cycle: the contexts take turns, the quiet one still sends
+        // every other cycle
+        for _ in 0..4 {
+            uart.tx_space.set(12);
+            mux.tick();
+        }
+        assert_eq!(2, mux.sent_count(1));
+        assert_eq!(2, mux.sent_count(2));
+        assert_eq!(0, mux.sent_count(3));
+    }
+}
@@ -1,12 +1,23 @@
+#![cfg(feature = "std")]
+
 #[cfg(test)]
 mod tests {
     extern crate min_rs as min;
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+    use std::sync::Mutex;
 
     struct Uart {
         tx_space_avaliable: u16,
     }
 
     impl Uart {
+        fn new(tx_space_avaliable: u16) -> Self {
+            Uart {
+                tx_space_avaliable,
+            }
+        }
+
         fn open(&self) {}
         fn close(&self) {}
         fn tx(&self, _byte: u8) {}
@@ -26,11 +37,148 @@ mod tests {
         }
     }
 
+    // tx buffer that is full for the first `busy_polls` queries, advancing the clock on each one
+    struct BusyUart<'a> {
+        busy_polls: Cell<u32>,
+        clock: &'a min::MockClock,
+    }
+
+    impl<'a> min::Interface for BusyUart<'a> {
+        fn tx_start(&self) {}
+        fn tx_finished(&self) {}
+        fn tx_space(&self) -> u16 {
+            self.clock.advance(1);
+            if self.busy_polls.get() > 0 {
+                self.busy_polls.set(self.busy_polls.get() - 1);
+                0
+            } else {
+                128
+            }
+        }
+        fn tx_byte(&self, _port: u8, _byte: u8) {}
+    }
+
+    // Counts the frames abandoned because of an error
+    struct ErrorCountingUart {
+        rx_errors: Cell<u32>,
+    }
+
+    impl min::Interface for ErrorCountingUart {
+        fn tx_start(&self) {}
+        fn tx_finished(&self) {}
+        fn tx_space(&self) -> u16 {
+            128
+        }
+        fn tx_byte(&self, _port: u8, _byte: u8) {}
+        fn on_rx_error(&self) {
+            self.rx_errors.set(self.rx_errors.get() + 1);
+        }
+    }
+
+    // tx buffer of 32 bytes, `tx_space` of them free
+    struct BoundedUart {
+        tx_space: Cell<u16>,
+    }
+
+    impl min::Interface for BoundedUart {
+        fn tx_start(&self) {}
+        fn tx_finished(&self) {}
+        fn tx_space(&self) -> u16 {
+            self.tx_space.get()
+        }
+        fn tx_byte(&self, _port: u8, _byte: u8) {
+            self.tx_space.set(self.tx_space.get().saturating_sub(1));
+        }
+        fn tx_capacity(&self) -> Option<u16> {
+            Some(32)
+        }
+    }
+
+    // Counts the tx space queries
+    struct SpaceCountingUart {
+        tx_space_calls: Cell<u32>,
+    }
+
+    impl min::Interface for SpaceCountingUart {
+        fn tx_start(&self) {}
+        fn tx_finished(&self) {}
+        fn tx_space(&self) -> u16 {
+            self.tx_space_calls.set(self.tx_space_calls.get() + 1);
+            128
+        }
+        fn tx_byte(&self, _port: u8, _byte: u8) {}
+    }
+
+    // Records the port of each frame sent
+    struct PortUart {
+        ports: RefCell<Vec<u8>>,
+        frame_started: Cell<bool>,
+    }
+
+    impl min::Interface for PortUart {
+        fn tx_start(&self) {
+            self.frame_started.set(true);
+        }
+        fn tx_finished(&self) {}
+        fn tx_space(&self) -> u16 {
+            128
+        }
+        fn tx_byte(&self, port: u8, _byte: u8) {
+            if self.frame_started.replace(false) {
+                self.ports.borrow_mut().push(port);
+            }
+        }
+    }
+
+    // Keeps the log records, as "target: message"
+    struct CaptureLogger;
+
+    static LOGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    static LOGGER: CaptureLogger = CaptureLogger;
+
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, _metadata: &log::Metadata) -> bool {
+            true
+        }
+        fn log(&self, record: &log::Record) {
+            LOGS.lock().unwrap().push(format!("{}: {}", record.target(), record.args()));
+        }
+        fn flush(&self) {}
+    }
+
+    // Leaves tx_space to the default, counts the frames sent
+    struct MinimalUart {
+        frames: Cell<u32>,
+    }
+
+    impl min::Interface for MinimalUart {
+        fn tx_start(&self) {
+            self.frames.set(self.frames.get() + 1);
+        }
+        fn tx_finished(&self) {}
+        fn tx_byte(&self, _port: u8, _byte: u8) {}
+    }
+
+    // Records the bytes sent, readable after the interface is handed over
+    struct RecordingUart {
+        tx_space: u16,
+        sent: Rc<RefCell<Vec<u8>>>,
+    }
+
+    impl min::Interface for RecordingUart {
+        fn tx_start(&self) {}
+        fn tx_finished(&self) {}
+        fn tx_space(&self) -> u16 {
+            self.tx_space
+        }
+        fn tx_byte(&self, _port: u8, byte: u8) {
+            self.sent.borrow_mut().push(byte);
+        }
+    }
+
     #[test]
     fn send() {
-        let uart = Uart{
-            tx_space_avaliable: 128,
-        };
+        let uart = Uart::new(128);
         let id: u8 = 0;
         let payload: [u8; 255] = [0; 255];
         let len: u8 = 8;
@@ -55,9 +203,7 @@ mod tests {
 
     #[test]
     fn send_no_enough_buffer() {
-        let uart = Uart{
-            tx_space_avaliable: 128,
-        };
+        let uart = Uart::new(128);
         let overfllow: u16 = 1;
         let id: u8 = 0;
         let payload: [u8; 255] = [0; 255];
@@ -74,7 +220,7 @@ mod tests {
         let oversized = match min.send_frame(id, &payload, len) {
             Ok(_) => 0,
             Err(min::Error::NoEnoughTxSpace(size)) => size,
-            Err(min::Error::NoMsg) => 0,
+            Err(_) => 0,
         };
 
         uart.close();
@@ -84,9 +230,7 @@ mod tests {
 
     #[test]
     fn receive() {
-        let uart = Uart{
-            tx_space_avaliable: 128,
-        };
+        let uart = Uart::new(128);
         let payload: [u8; 19] = [
             0xaa, 0xaa, 0xaa,   // SOF
             0x00,   // ID/control
@@ -111,11 +255,51 @@ mod tests {
         assert_eq!(0x38838f82, min.get_rx_frame_checksum());
     }
 
+    #[test]
+    fn receive_byte_by_byte() {
+        let uart = Uart::new(128);
+        // Same frame as in `receive`, including a stuff byte
+        const PAYLOAD: [u8; 19] = min::min_frame!(id = 0, payload = [0xaa, 0xaa, 0xaa, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        let payload = PAYLOAD;
+        let mut bulk = min::Context::new(
+            String::from("bulk"),
+            &uart,
+            0,
+            false,
+        );
+        let mut bytes = min::Context::new(
+            String::from("bytes"),
+            &uart,
+            0,
+            false,
+        );
+
+        uart.open();
+
+        bulk.poll(&payload, payload.len() as u32);
+        let expected = bulk.get_msg().ok().unwrap();
+
+        // One byte per poll, as interrupt-driven reception does
+        for byte in payload.iter() {
+            bytes.poll(&[*byte], 1);
+        }
+        assert_eq!(0x38838f82, bytes.get_rx_frame_checksum());
+        assert_eq!(bulk.get_rx_frame_checksum(), bytes.get_rx_frame_checksum());
+        assert_eq!(Some(expected.clone()), bytes.get_msg().ok());
+
+        // Split in two at every position, including inside the header and after a stuff byte
+        for split in 0..payload.len() {
+            bytes.poll(&payload[..split], split as u32);
+            bytes.poll(&payload[split..], (payload.len() - split) as u32);
+            assert_eq!(Some(expected.clone()), bytes.get_msg().ok(), "split at {}", split);
+        }
+
+        uart.close();
+    }
+
     #[test]
     fn restart_receiving() {
-        let uart = Uart{
-            tx_space_avaliable: 128,
-        };
+        let uart = Uart::new(128);
         let payload: [u8; 24] = [
             0xaa, 0xaa, 0xaa,   // SOF
             0x00,   // ID/control
@@ -146,9 +330,7 @@ mod tests {
 
     #[test]
     fn receive_frame_error_length() {
-        let uart = Uart{
-            tx_space_avaliable: 128,
-        };
+        let uart = Uart::new(128);
         let payload: [u8; 19] = [
             0xaa, 0xaa, 0xaa,   // SOF
             0x00,   // ID/control
@@ -180,9 +362,7 @@ mod tests {
 
     #[test]
     fn receive_frame_error_checksum() {
-        let uart = Uart{
-            tx_space_avaliable: 128,
-        };
+        let uart = Uart::new(128);
         let payload: [u8; 19] = [
             0xaa, 0xaa, 0xaa,   // SOF
             0x00,   // ID/control
@@ -209,4 +389,1262 @@ mod tests {
         // 0x38838f82 is the correct checksum
         assert_eq!(0x38838f82, min.get_rx_checksum());
     }
+
+    #[test]
+    fn receive_rate_limited() {
+        let uart = Uart::new(128);
+        let payload: [u8; 19] = [
+            0xaa, 0xaa, 0xaa,   // SOF
+            0x00,   // ID/control
+            0x08,   // Length
+            0xaa, 0xaa, 0x55, 0xaa, 0x00, 0x00, 0x00, 0x00, 0x00,   // Data(including a stuff-byte 0x55)
+            0x38, 0x83, 0x8f, 0x82, // CRC checksum
+            0x55,   // EOF
+        ];
+        let clock = min::MockClock::new(0);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+        min.set_clock(&clock);
+        min.set_max_rx_frames_per_sec(Some(2));
+
+        uart.open();
+
+        for _ in 0..3 {
+            min.poll(&payload, payload.len() as u32);
+        }
+        assert_eq!(1, min.get_rate_limited_drop_cnt());
+
+        clock.advance(1000);
+        min.poll(&payload, payload.len() as u32);
+        assert_eq!(1, min.get_rate_limited_drop_cnt());
+
+        uart.close();
+
+        let mut received = 0;
+        while min.get_msg().is_ok() {
+            received += 1;
+        }
+        assert_eq!(3, received);
+    }
+
+    #[test]
+    fn clear_msgs() {
+        let uart = Uart::new(128);
+        let payload: [u8; 19] = [
+            0xaa, 0xaa, 0xaa,   // SOF
+            0x00,   // ID/control
+            0x08,   // Length
+            0xaa, 0xaa, 0x55, 0xaa, 0x00, 0x00, 0x00, 0x00, 0x00,   // Data(including a stuff-byte 0x55)
+            0x38, 0x83, 0x8f, 0x82, // CRC checksum
+            0x55,   // EOF
+        ];
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        uart.open();
+
+        min.poll(&payload, payload.len() as u32);
+        min.poll(&payload, payload.len() as u32);
+
+        uart.close();
+
+        assert_eq!(2, min.clear_msgs());
+        assert_eq!(0, min.clear_msgs());
+        assert_eq!(2, min.get_discarded_msg_cnt());
+        assert!(min.get_msg().is_err());
+    }
+
+    #[test]
+    fn send_blocking() {
+        let clock = min::MockClock::new(0);
+        let uart = BusyUart{
+            busy_polls: Cell::new(3),
+            clock: &clock,
+        };
+        let payload: [u8; 8] = [0; 8];
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+        min.set_clock(&clock);
+
+        let sent = match min.send_frame_blocking(0, &payload, 8, 10) {
+            Ok(size) => size,
+            Err(_) => panic!("Opps!"),
+        };
+        assert_eq!(8, sent);
+        assert_eq!(0, uart.busy_polls.get());
+
+        uart.busy_polls.set(100);
+        let oversized = match min.send_frame_blocking(0, &payload, 8, 10) {
+            Ok(_) => 0,
+            Err(min::Error::NoEnoughTxSpace(size)) => size,
+            Err(_) => 0,
+        };
+        assert_eq!(8, oversized);
+        assert!(uart.busy_polls.get() > 0);
+    }
+
+    #[test]
+    fn process() {
+        let uart = Uart::new(128);
+        let payload: [u8; 19] = [
+            0xaa, 0xaa, 0xaa,   // SOF
+            0x00,   // ID/control
+            0x08,   // Length
+            0xaa, 0xaa, 0x55, 0xaa, 0x00, 0x00, 0x00, 0x00, 0x00,   // Data(including a stuff-byte 0x55)
+            0x38, 0x83, 0x8f, 0x82, // CRC checksum
+            0x55,   // EOF
+        ];
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        uart.open();
+
+        let msgs = min.process(&payload[0..10]);
+        assert!(msgs.is_empty());
+
+        let mut bytes = payload[10..].to_vec();
+        bytes.extend_from_slice(&payload);
+        let msgs = min.process(&bytes);
+
+        uart.close();
+
+        assert_eq!(2, msgs.len());
+        for msg in msgs {
+            assert_eq!(vec![0xaa, 0xaa, 0xaa, 0x00, 0x00, 0x00, 0x00, 0x00], msg.buf);
+        }
+        assert!(min.get_msg().is_err());
+    }
+
+    #[test]
+    fn receive_frame_error_eof() {
+        let uart = Uart::new(128);
+        let payload: [u8; 19] = [
+            0xaa, 0xaa, 0xaa,   // SOF
+            0x00,   // ID/control
+            0x08,   // Length
+            0xaa, 0xaa, 0x55, 0xaa, 0x00, 0x00, 0x00, 0x00, 0x00,   // Data(including a stuff-byte 0x55)
+            0x38, 0x83, 0x8f, 0x82, // CRC checksum
+            0x00,   // EOF(correct value is 0x55)
+        ];
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        uart.open();
+
+        min.poll(&payload, payload.len() as u32);
+
+        uart.close();
+
+        assert_eq!(1, min.get_eof_error_cnt());
+        assert_eq!(0, min.get_crc_error_cnt());
+        assert!(min.get_msg().is_err());
+    }
+
+    #[test]
+    fn receive_frame_timeout() {
+        let uart = Uart::new(128);
+        let payload: [u8; 19] = [
+            0xaa, 0xaa, 0xaa,   // SOF
+            0x00,   // ID/control
+            0x08,   // Length
+            0xaa, 0xaa, 0x55, 0xaa, 0x00, 0x00, 0x00, 0x00, 0x00,   // Data(including a stuff-byte 0x55)
+            0x38, 0x83, 0x8f, 0x82, // CRC checksum
+            0x55,   // EOF
+        ];
+        let clock = min::MockClock::new(0);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+        min.set_clock(&clock);
+        min.set_rx_frame_timeout(Some(50));
+
+        uart.open();
+
+        min.poll(&payload[0..10], 10);
+        clock.advance(49);
+        min.poll(&[0][0..0], 0);
+        assert_eq!(0, min.get_rx_frame_timeout_cnt());
+
+        clock.advance(1);
+        min.poll(&[0][0..0], 0);
+        assert_eq!(1, min.get_rx_frame_timeout_cnt());
+
+        // The rest of the frame is ignored
+        min.poll(&payload[10..], 9);
+        assert!(min.get_msg().is_err());
+
+        // A new frame is received as usual
+        min.poll(&payload, payload.len() as u32);
+        assert!(min.get_msg().is_ok());
+
+        uart.close();
+    }
+
+    #[test]
+    fn iterate_msgs() {
+        let uart = Uart::new(128);
+        let payload: [u8; 19] = [
+            0xaa, 0xaa, 0xaa,   // SOF
+            0x00,   // ID/control
+            0x08,   // Length
+            0xaa, 0xaa, 0x55, 0xaa, 0x00, 0x00, 0x00, 0x00, 0x00,   // Data(including a stuff-byte 0x55)
+            0x38, 0x83, 0x8f, 0x82, // CRC checksum
+            0x55,   // EOF
+        ];
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        uart.open();
+
+        min.poll(&payload, payload.len() as u32);
+        min.poll(&payload, payload.len() as u32);
+        assert_eq!(1, min.messages().take(1).count());
+
+        min.poll(&payload, payload.len() as u32);
+        let mut received = 0;
+        for msg in &mut min {
+            assert_eq!(8, msg.len);
+            received += 1;
+        }
+        assert_eq!(2, received);
+        assert_eq!(0, min.messages().count());
+
+        uart.close();
+    }
+
+    #[test]
+    fn rx_trace() {
+        let uart = Uart::new(128);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        uart.open();
+
+        min.receive(&[0x01, 0x02]);
+        assert!(min.rx_trace().is_empty());
+
+        min.set_rx_trace_len(4);
+        min.receive(&[0x03, 0x04, 0x05]);
+        assert_eq!(&[0x03, 0x04, 0x05], min.rx_trace());
+
+        let bytes: Vec<u8> = (0x06..0x10).collect();
+        min.receive(&bytes);
+        assert_eq!(&[0x0c, 0x0d, 0x0e, 0x0f], min.rx_trace());
+
+        min.set_rx_trace_len(0);
+        min.receive(&[0x10]);
+        assert!(min.rx_trace().is_empty());
+
+        uart.close();
+    }
+
+    #[test]
+    fn send_payload() {
+        let uart = Uart::new(1024);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        uart.open();
+
+        assert_eq!(3, min.send(0, [0x01, 0x02, 0x03]).ok().unwrap());
+        assert_eq!(2, min.send(0, &[0x01, 0x02][..]).ok().unwrap());
+        assert_eq!(4, min.send(0, vec![0x01; 4]).ok().unwrap());
+        assert_eq!(255, min.send(0, vec![0x01; 255]).ok().unwrap());
+
+        let too_long = match min.send(0, vec![0x01; 256]) {
+            Err(min::Error::PayloadTooLong(len)) => len,
+            _ => 0,
+        };
+        assert_eq!(256, too_long);
+
+        uart.close();
+    }
+
+    #[test]
+    fn encode_into_writer() {
+        let uart = Uart::new(1024);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        let payload = [0xaa, 0xaa, 0xaa, 0x01];
+        let frame = min.encode_frame(0x25, &payload).ok().unwrap();
+        let mut buf: Vec<u8> = Vec::new();
+        assert_eq!(frame.len(), min.encode_into(&mut buf, 0x25, &payload).unwrap());
+        assert_eq!(frame, buf);
+
+        min.receive(&buf);
+        let msgs: Vec<min::Msg> = min.drain_msgs().collect();
+        assert_eq!(1, msgs.len());
+        assert_eq!(0x25, msgs[0].min_id);
+        assert_eq!(payload.to_vec(), msgs[0].buf);
+
+        min.set_crc_covers_eof(true);
+        let frame = min.encode_frame(0x25, &payload).ok().unwrap();
+        buf.clear();
+        min.encode_into(&mut buf, 0x25, &payload).unwrap();
+        assert_eq!(frame, buf);
+
+        // Every framing option goes through the same encoder
+        min.set_header_len(2);
+        min.set_header_crc(true);
+        let frame = min.encode_frame(0x25, &payload).ok().unwrap();
+        buf.clear();
+        assert_eq!(min.frame_wire_len(0x25, &payload), min.encode_into(&mut buf, 0x25, &payload).unwrap());
+        assert_eq!(frame, buf);
+        min.set_transparent_framing(true);
+        let frame = min.encode_frame(0x25, &payload).ok().unwrap();
+        buf.clear();
+        assert_eq!(min.frame_wire_len(0x25, &payload), min.encode_into(&mut buf, 0x25, &payload).unwrap());
+        assert_eq!(frame, buf);
+
+        assert!(min.encode_into(&mut buf, 0, &[0; 256]).is_err());
+    }
+
+    #[test]
+    fn stuffing_overhead() {
+        let uart = Uart::new(1024);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        uart.open();
+
+        assert_eq!(0.0, min.stuffing_overhead_ratio());
+
+        // Header, ID, length, checksum and EOF: 10 bytes
+        let payload = [0x01; 100];
+        min.send(0, payload).ok().unwrap();
+        assert_eq!(100, min.get_payload_bytes_sent_cnt());
+        assert_eq!(110, min.get_wire_bytes_sent_cnt());
+        assert_eq!(0.1, min.stuffing_overhead_ratio());
+
+        // A stuff byte every 2 bytes of the payload
+        let payload = [0xaa; 100];
+        let wire_len = min.frame_wire_len(0, &payload) as u64;
+        assert_eq!(160, wire_len);
+        min.send(0, payload).ok().unwrap();
+        assert_eq!(200, min.get_payload_bytes_sent_cnt());
+        assert_eq!(110 + wire_len, min.get_wire_bytes_sent_cnt());
+        assert_eq!(0.35, min.stuffing_overhead_ratio());
+
+        // Encoding doesn't count
+        min.encode_frame(0, &payload).ok().unwrap();
+        assert_eq!(200, min.get_payload_bytes_sent_cnt());
+
+        uart.close();
+    }
+
+    #[test]
+    fn recv_frame() {
+        let uart = Uart::new(1024);
+        let clock = min::MockClock::new(0);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+        min.set_clock(&clock);
+
+        let mut wire = min.encode_frame(0x12, &[0x01, 0x02, 0x03]).ok().unwrap();
+        wire.extend(min.encode_frame(0x13, &[0x04]).ok().unwrap());
+
+        // The bytes trickle in 4 at a time
+        let mut offset = 0;
+        let mut read = |buf: &mut [u8]| {
+            let len = (wire.len() - offset).min(4);
+            buf[..len].copy_from_slice(&wire[offset..offset + len]);
+            offset += len;
+            len
+        };
+        let msg = min.recv_frame(&mut read, 1000).ok().unwrap();
+        assert_eq!(0x12, msg.min_id);
+        assert_eq!(vec![0x01, 0x02, 0x03], msg.buf);
+        let msg = min.recv_frame(&mut read, 1000).ok().unwrap();
+        assert_eq!(0x13, msg.min_id);
+
+        // Nothing more to read
+        let result = min.recv_frame(|_: &mut [u8]| {
+            clock.advance(5);
+            0
+        }, 20);
+        assert!(matches!(result, Err(min::Error::NoMsg)));
+        assert_eq!(20, min::Clock::now_ms(&clock));
+    }
+
+    #[test]
+    fn recent_error_histogram() {
+        let uart = Uart::new(1024);
+        let clock = min::MockClock::new(0);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+        min.set_clock(&clock);
+
+        let frame = min.encode_frame(0x01, &[0x01, 0x02]).ok().unwrap();
+        let eof = frame.len() - 1;
+        let mut crc_error = frame.clone();
+        crc_error[eof - 1] ^= 0x01;
+        let mut eof_error = frame.clone();
+        eof_error[eof] = 0x00;
+
+        min.receive(&crc_error);
+        clock.advance(25_000);
+        min.receive(&eof_error);
+        min.receive(&crc_error);
+        let histogram = min.recent_error_histogram();
+        assert_eq!(2, histogram.crc_errors);
+        assert_eq!(1, histogram.eof_errors);
+        assert_eq!(0, histogram.length_errors);
+        assert_eq!(0, histogram.sequence_mismatches);
+
+        // The first bucket leaves the window
+        clock.set(59_999);
+        assert_eq!(2, min.recent_error_histogram().crc_errors);
+        clock.set(60_000);
+        assert_eq!(1, min.recent_error_histogram().crc_errors);
+        assert_eq!(1, min.recent_error_histogram().eof_errors);
+
+        // Its slot is reused
+        min.receive(&crc_error);
+        assert_eq!(2, min.recent_error_histogram().crc_errors);
+
+        clock.set(80_000);
+        let histogram = min.recent_error_histogram();
+        assert_eq!(1, histogram.crc_errors);
+        assert_eq!(0, histogram.eof_errors);
+        clock.set(120_000);
+        assert_eq!(min::ErrorHistogram::default(), min.recent_error_histogram());
+
+        // The cumulative counters are kept
+        assert_eq!(3, min.get_crc_error_cnt());
+        assert_eq!(1, min.get_eof_error_cnt());
+    }
+
+    #[test]
+    fn receive_transport_frame_when_disabled() {
+        let frame: [u8; 19] = [
+            0xaa, 0xaa, 0xaa,
+            0x80,
+            0x01,   // Sequence
+            0x08,
+            0xbb, 0xbb, 0xbb, 0x00, 0x00, 0x00, 0x00, 0x01,
+            0xe6, 0x98, 0x4f, 0xde,
+            0x55
+        ];
+        let uart = Uart::new(128);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        uart.open();
+
+        assert!(min.process(&frame).is_empty());
+        assert_eq!(1, min.get_transport_unsupported_drop_cnt());
+
+        min.set_accept_transport_when_disabled(true);
+        let msgs = min.process(&frame);
+        assert_eq!(1, msgs.len());
+        assert_eq!(0x00, msgs[0].control_byte);
+        assert!(msgs[0].is_transport);
+        assert_eq!(&[0xbb, 0xbb, 0xbb, 0x00, 0x00, 0x00, 0x00, 0x01], &msgs[0].buf[..]);
+        assert_eq!(1, min.get_transport_unsupported_drop_cnt());
+
+        uart.close();
+    }
+
+    #[test]
+    fn last_frame_raw() {
+        let uart = Uart::new(128);
+        let frame: [u8; 19] = [
+            0xaa, 0xaa, 0xaa,   // SOF
+            0x00,   // ID/control
+            0x08,   // Length
+            0xaa, 0xaa, 0x55, 0xaa, 0x00, 0x00, 0x00, 0x00, 0x00,   // Data(including a stuff-byte 0x55)
+            0x38, 0x83, 0x8f, 0x82, // CRC checksum
+            0x55,   // EOF
+        ];
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        uart.open();
+
+        min.receive(&frame);
+        assert!(min.last_frame_raw().is_empty());
+
+        min.set_keep_last_frame_raw(true);
+        // Noise before the frame is not kept
+        min.receive(&[0x01, 0xaa, 0x02]);
+        min.receive(&frame[0..10]);
+        assert!(min.last_frame_raw().is_empty());
+        min.receive(&frame[10..]);
+        assert_eq!(&frame[..], min.last_frame_raw());
+
+        let mut bad_crc = frame;
+        bad_crc[17] = 0x00;
+        min.receive(&bad_crc);
+        assert_eq!(&bad_crc[0..18], min.last_frame_raw());
+        assert_eq!(1, min.get_crc_error_cnt());
+
+        uart.close();
+    }
+
+    #[test]
+    fn rename() {
+        let uart = Uart::new(128);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        assert_eq!("min", min.name());
+        min.set_name(String::from("peer-42"));
+        assert_eq!("peer-42", min.name());
+    }
+
+    #[test]
+    fn capabilities() {
+        let uart = Uart::new(128);
+        let min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        assert_eq!(255, min.max_payload());
+        assert_eq!(16, min.max_window());
+        assert_eq!(31, min.fifo_capacity());
+        assert!(!min.transport_enabled());
+    }
+
+    #[test]
+    fn interface_rx_errors() {
+        let uart = ErrorCountingUart{
+            rx_errors: Cell::new(0),
+        };
+        let frame: [u8; 19] = [
+            0xaa, 0xaa, 0xaa,   // SOF
+            0x00,   // ID/control
+            0x08,   // Length
+            0xaa, 0xaa, 0x55, 0xaa, 0x00, 0x00, 0x00, 0x00, 0x00,   // Data(including a stuff-byte 0x55)
+            0x38, 0x83, 0x8f, 0x82, // CRC checksum
+            0x55,   // EOF
+        ];
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        // Valid frame and noise between frames
+        min.receive(&frame);
+        min.receive(&[0xaa, 0xaa, 0x01]);
+        assert_eq!(0, uart.rx_errors.get());
+
+        let mut bad_crc = frame;
+        bad_crc[14] = 0x00;
+        min.receive(&bad_crc);
+        assert_eq!(1, uart.rx_errors.get());
+
+        let mut bad_eof = frame;
+        bad_eof[18] = 0x00;
+        min.receive(&bad_eof);
+        assert_eq!(2, uart.rx_errors.get());
+
+        // Missing stuff byte
+        let mut bad_stuffing = frame;
+        bad_stuffing[7] = 0x00;
+        min.receive(&bad_stuffing);
+        assert_eq!(3, uart.rx_errors.get());
+        assert!(min.get_msg().is_ok());
+        assert!(min.get_msg().is_err());
+    }
+
+    #[test]
+    fn drain_errors() {
+        let uart = Uart::new(128);
+        let frame: [u8; 19] = [
+            0xaa, 0xaa, 0xaa,   // SOF
+            0x00,   // ID/control
+            0x08,   // Length
+            0xaa, 0xaa, 0x55, 0xaa, 0x00, 0x00, 0x00, 0x00, 0x00,   // Data(including a stuff-byte 0x55)
+            0x38, 0x83, 0x8f, 0x82, // CRC checksum
+            0x55,   // EOF
+        ];
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        min.receive(&frame);
+        assert_eq!(0, min.drain_errors().count());
+
+        let mut bad_crc = frame;
+        bad_crc[14] = 0x00;
+        min.receive(&bad_crc);
+        let mut bad_eof = frame;
+        bad_eof[18] = 0x00;
+        min.receive(&bad_eof);
+        let mut bad_stuffing = frame;
+        bad_stuffing[7] = 0x00;
+        min.receive(&bad_stuffing);
+
+        let errors: Vec<min::FrameError> = min.drain_errors().collect();
+        assert_eq!(vec![
+            min::FrameError::Crc { expected: 0x38838f82, actual: 0x00838f82 },
+            min::FrameError::Eof { byte: 0x00 },
+            min::FrameError::Stuffing { byte: 0x00 },
+        ], errors);
+        assert_eq!(0, min.drain_errors().count());
+
+        // Only the last ones are kept
+        for _ in 0..(min::MAX_FRAME_ERRORS + 8) {
+            min.receive(&bad_eof);
+        }
+        assert_eq!(min::MAX_FRAME_ERRORS, min.drain_errors().count());
+    }
+
+    #[test]
+    fn tx_port_rotation() {
+        let uart = PortUart {
+            ports: RefCell::new(Vec::new()),
+            frame_started: Cell::new(false),
+        };
+        let payload: [u8; 4] = [1, 2, 3, 4];
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+        min.send_frame(0, &payload, 4).ok().unwrap();
+        min.set_tx_port_rotation(&[1, 2, 3]);
+        for _ in 0..4 {
+            min.send_frame(0, &payload, 4).ok().unwrap();
+        }
+        // An explicit port doesn't move the rotation
+        min.send_frame_on(7, 0, &payload, 4).ok().unwrap();
+        min.send_frame(0, &payload, 4).ok().unwrap();
+        min.set_tx_port_rotation(&[]);
+        min.send_frame(0, &payload, 4).ok().unwrap();
+        assert_eq!(vec![0, 1, 2, 3, 1, 7, 2, 0], *uart.ports.borrow());
+    }
+
+    #[test]
+    fn tx_space_batch() {
+        const FRAME: [u8; 12] = min::min_frame!(id = 1, payload = [0x01, 0x02]);
+        let uart = SpaceCountingUart {
+            tx_space_calls: Cell::new(0),
+        };
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+        min.set_on_rx_frame(|msg| min::Reaction::QueueReply(msg.min_id, msg.buf.clone()));
+
+        // Three replies sent in one pass, a single query
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            received.extend_from_slice(&FRAME);
+        }
+        min.receive(&received);
+        assert_eq!(1, uart.tx_space_calls.get());
+
+        // The next pass queries again
+        min.receive(&FRAME);
+        assert_eq!(2, uart.tx_space_calls.get());
+
+        // 128 bytes are not enough for 11 replies of 12 bytes: queried again when exhausted
+        let mut received = Vec::new();
+        for _ in 0..11 {
+            received.extend_from_slice(&FRAME);
+        }
+        min.receive(&received);
+        assert_eq!(4, uart.tx_space_calls.get());
+    }
+
+    #[test]
+    fn process_until() {
+        let uart = Uart::new(1024);
+        let clock = min::MockClock::new(0);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+        min.set_clock(&clock);
+
+        let mut wire = min.encode_frame(0x12, &[0x01]).ok().unwrap();
+        wire.extend(min.encode_frame(0x20, &[0x02]).ok().unwrap());
+        wire.extend(min.encode_frame(0x13, &[0x03]).ok().unwrap());
+
+        let mut offset = 0;
+        let mut read = |buf: &mut [u8]| {
+            let len = (wire.len() - offset).min(4);
+            buf[..len].copy_from_slice(&wire[offset..offset + len]);
+            offset += len;
+            len
+        };
+        let msg = min.process_until(&mut read, |msg| msg.min_id == 0x20, 1000).ok().unwrap();
+        assert_eq!(vec![0x02], msg.buf);
+
+        // The other messages are left in the queue
+        let msgs = min.process(&[]);
+        assert_eq!(vec![0x12], msgs.iter().map(|msg| msg.min_id).collect::<Vec<u8>>());
+        let msg = min.process_until(&mut read, |msg| msg.min_id == 0x13, 1000).ok().unwrap();
+        assert_eq!(vec![0x03], msg.buf);
+
+        // No match
+        let result = min.process_until(|_: &mut [u8]| {
+            clock.advance(5);
+            0
+        }, |msg| msg.min_id == 0x20, 20);
+        assert!(matches!(result, Err(min::Error::NoMsg)));
+    }
+
+    #[test]
+    fn process_until_drop_oldest() {
+        let uart = Uart::new(1024);
+        let clock = min::MockClock::new(0);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+        min.set_clock(&clock);
+        min.set_msg_queue_capacity(Some(2), min::OverflowPolicy::DropOldest);
+
+        let mut first = min.encode_frame(0x10, &[0x00]).ok().unwrap();
+        first.extend(min.encode_frame(0x11, &[0x00]).ok().unwrap());
+        // Popped from the end, one entry per read
+        let mut reads = vec![min.encode_frame(0x12, &[0x01]).ok().unwrap(), first];
+
+        // The queue is full after the first read, the next ones drop its front
+        let msg = min.process_until(|buf: &mut [u8]| {
+            clock.advance(5);
+            match reads.pop() {
+                Some(wire) => {
+                    buf[..wire.len()].copy_from_slice(&wire);
+                    wire.len()
+                },
+                None => 0,
+            }
+        }, |msg| msg.min_id == 0x12, 100).ok().unwrap();
+        assert_eq!(vec![0x01], msg.buf);
+        assert_eq!(1, min.get_msg_overflow_drop_cnt());
+    }
+
+    #[test]
+    fn header_crc() {
+        let uart = Uart::new(1024);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+        min.set_header_crc(true);
+
+        let frame = min.encode_frame(0x12, &[0x01, 0x02, 0x03]).ok().unwrap();
+        // Header checksum after the length
+        assert_eq!(14, frame.len());
+        let msgs = min.process(&frame);
+        assert_eq!(1, msgs.len());
+        assert_eq!(vec![0x01, 0x02, 0x03], msgs[0].buf);
+
+        // A corrupted length is caught before the payload is read
+        let mut corrupted = frame.clone();
+        corrupted[4] = 0x40;
+        min.receive(&corrupted[..6]);
+        assert_eq!(1, min.get_header_crc_error_cnt());
+        let errors = min.drain_errors().collect::<Vec<min::FrameError>>();
+        assert!(matches!(errors[..], [min::FrameError::HeaderCrc { .. }]));
+        min.receive(&corrupted[6..]);
+        assert!(min.get_msg().is_err());
+        assert_eq!(0, min.get_crc_error_cnt());
+    }
+
+    #[test]
+    fn transparent_framing() {
+        let uart = Uart::new(1024);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+        let payload = [0xaa, 0xaa, 0xaa, 0x01];
+        min.set_transparent_framing(true);
+
+        // No stuff byte
+        let frame = min.encode_frame(0x12, &payload).ok().unwrap();
+        assert_eq!(14, frame.len());
+        assert_eq!(&payload, &frame[5..9]);
+        assert_eq!(frame.len(), min.frame_wire_len(0x12, &payload));
+        let msgs = min.process(&frame);
+        assert_eq!(1, msgs.len());
+        assert_eq!(payload.to_vec(), msgs[0].buf);
+
+        // The header in the payload restarts the frame otherwise
+        min.set_transparent_framing(false);
+        assert!(min.process(&frame).is_empty());
+    }
+
+    #[test]
+    fn try_send_frame() {
+        let uart = BoundedUart {
+            tx_space: Cell::new(32),
+        };
+        let payload = [0_u8; 32];
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        assert_eq!(Some(16), min.try_send_frame(0, &payload, 16).ok());
+        // Transient: retry once the buffer is drained
+        assert!(matches!(min.try_send_frame(0, &payload, 16), Err(min::Error::WouldBlock)));
+        uart.tx_space.set(32);
+        assert_eq!(Some(16), min.try_send_frame(0, &payload, 16).ok());
+
+        // Permanent
+        uart.tx_space.set(32);
+        assert!(matches!(min.try_send_frame(0, &payload, 22), Err(min::Error::FrameTooLarge(33))));
+    }
+
+    #[test]
+    fn rx_totals() {
+        let uart = Uart::new(1024);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        let mut wire = min.encode_frame(0x12, &[0x01, 0x02]).ok().unwrap();
+        wire.extend(min.encode_frame(0x13, &[0x03]).ok().unwrap());
+        // Noise between the frames
+        wire.extend_from_slice(&[0x00, 0x01]);
+        wire.extend(min.encode_frame(0x14, &[]).ok().unwrap());
+        assert_eq!(35, wire.len());
+        min.receive(&wire[..20]);
+        min.receive(&wire[20..]);
+        assert_eq!(35, min.rx_bytes_total());
+        assert_eq!(3, min.rx_frames_total());
+    }
+
+    #[test]
+    fn msg_queue_overflow() {
+        let uart = Uart::new(1024);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        min.set_msg_queue_capacity(Some(1), min::OverflowPolicy::DropNewest);
+        min.receive(&min::min_frame!(id = 1, payload = [0x01]));
+        min.receive(&min::min_frame!(id = 2, payload = [0x02]));
+        assert_eq!(1, min.get_msg_overflow_drop_cnt());
+        assert_eq!(1, min.get_msg().ok().unwrap().min_id);
+        assert!(min.get_msg().is_err());
+
+        min.set_msg_queue_capacity(Some(1), min::OverflowPolicy::DropOldest);
+        min.receive(&min::min_frame!(id = 1, payload = [0x01]));
+        min.receive(&min::min_frame!(id = 2, payload = [0x02]));
+        assert_eq!(2, min.get_msg_overflow_drop_cnt());
+        assert_eq!(2, min.get_msg().ok().unwrap().min_id);
+        assert!(min.get_msg().is_err());
+
+        // Unbounded
+        min.set_msg_queue_capacity(None, min::OverflowPolicy::DropNewest);
+        for _ in 0..200 {
+            min.receive(&min::min_frame!(id = 1, payload = [0x01]));
+        }
+        assert_eq!(200, min.drain_msgs().count());
+        assert_eq!(2, min.get_msg_overflow_drop_cnt());
+    }
+
+    #[test]
+    fn partial_frame() {
+        const FRAME: [u8; 12] = min::min_frame!(id = 1, payload = [0x01, 0x02]);
+        let uart = Uart::new(1024);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+        assert!(!min.has_partial_frame());
+        assert_eq!(0, min.partial_frame_bytes());
+
+        // Noise and two header bytes don't start a frame
+        min.receive(&[0x00, 0x01]);
+        min.receive(&FRAME[..2]);
+        assert!(!min.has_partial_frame());
+
+        min.receive(&FRAME[2..3]);
+        assert!(min.has_partial_frame());
+        assert_eq!(3, min.partial_frame_bytes());
+        min.receive(&FRAME[3..11]);
+        assert!(min.has_partial_frame());
+        assert_eq!(11, min.partial_frame_bytes());
+        assert!(min.get_msg().is_err());
+
+        // The EOF completes it
+        min.receive(&FRAME[11..]);
+        assert!(!min.has_partial_frame());
+        assert_eq!(0, min.partial_frame_bytes());
+        assert_eq!(vec![0x01, 0x02], min.get_msg().ok().unwrap().buf);
+    }
+
+    #[test]
+    fn accepted_ports() {
+        const FRAME: [u8; 11] = min::min_frame!(id = 1, payload = [0x01]);
+        let uart = Uart::new(1024);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        // All ports accepted by default
+        min.receive_on(5, &FRAME);
+        assert_eq!(5, min.get_msg().ok().unwrap().port);
+
+        min.set_accepted_ports(&[0, 2]);
+        min.receive_on(1, &FRAME);
+        min.receive_on(2, &FRAME);
+        min.receive(&FRAME);
+        min.receive_on(3, &FRAME[..5]);
+        min.receive_on(2, &FRAME[5..]);
+        let ports: Vec<u8> = min.drain_msgs().map(|msg| msg.port).collect();
+        assert_eq!(vec![2, 0, 2], ports);
+        assert_eq!(1, min.get_port_filtered_drop_cnt());
+        assert_eq!(0, min.get_filtered_drop_cnt());
+
+        min.set_accepted_ports(&[]);
+        min.receive_on(1, &FRAME);
+        assert_eq!(1, min.get_msg().ok().unwrap().port);
+    }
+
+    #[test]
+    fn log_payloads() {
+        const FRAME: [u8; 12] = min::min_frame!(id = 1, payload = [0x5a, 0xc3]);
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Debug);
+        let logs = |target: &str| -> Vec<String> {
+            LOGS.lock().unwrap().iter().filter(|line| line.starts_with(target)).cloned().collect()
+        };
+        let uart = Uart::new(1024);
+
+        let mut min = min::Context::new(
+            String::from("payloads_off"),
+            &uart,
+            0,
+            false,
+        );
+        min.receive(&FRAME);
+        min.send_frame(2, &[0x5a, 0xc3], 2).ok().unwrap();
+        let lines = logs("payloads_off:");
+        assert_eq!(2, lines.len());
+        assert!(lines.iter().all(|line| !line.contains("0x5a")));
+
+        let mut min = min::Context::new(
+            String::from("payloads_on"),
+            &uart,
+            0,
+            false,
+        );
+        min.set_log_payloads(true);
+        min.receive(&FRAME);
+        min.send_frame(2, &[0x5a, 0xc3], 2).ok().unwrap();
+        assert_eq!(vec![
+            "payloads_on: Incoming app frame id=1, payload len=2, payload=[ 0x5a 0xc3 ]",
+            "payloads_on: send frame: id=2, len=2, payload=[ 0x5a 0xc3 ]",
+        ], logs("payloads_on:"));
+    }
+
+    // `debug-checks` asserts on the window instead of warning
+    #[cfg(not(feature = "debug-checks"))]
+    #[test]
+    fn seq_space_pressure_warning() {
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Debug);
+        let warnings = || -> usize {
+            LOGS.lock().unwrap().iter().filter(|line| line.starts_with("pressure:") && line.contains("sequence space")).count()
+        };
+        let uart = Uart::new(128);
+        let clock = min::MockClock::new(0);
+        let mut min = min::Context::new(
+            String::from("pressure"),
+            &uart,
+            0,
+            true,
+        );
+        min.set_clock(&clock);
+
+        // One new frame sent per poll, within the default window
+        for i in 0..8 {
+            min.queue_frame(1, &[i], 1).ok().unwrap();
+            min.poll(&[], 0);
+        }
+        assert_eq!(8.0 / 256.0, min.seq_space_pressure());
+        assert_eq!(0, warnings());
+
+        // A smaller window given while frames are in flight: warned about once
+        let config = min::TransportConfig {
+            window_size: 4,
+            ..min::TransportConfig::default()
+        };
+        let mut min = min.with_transport_config(config).ok().unwrap();
+        min.poll(&[], 0);
+        min.poll(&[], 0);
+        assert_eq!(8.0 / 256.0, min.seq_space_pressure());
+        assert_eq!(1, warnings());
+    }
+
+    #[test]
+    fn msg_crc() {
+        const FRAME1: [u8; 12] = min::min_frame!(id = 1, payload = [0x01, 0x02]);
+        const FRAME2: [u8; 12] = min::min_frame!(id = 2, payload = [0x03, 0x04]);
+        let uart = Uart::new(1024);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        min.receive(&FRAME1);
+        min.receive(&FRAME2);
+        let crc1 = min::crc32(&[0x01, 0x02, 0x01, 0x02]);
+        let crc2 = min::crc32(&[0x02, 0x02, 0x03, 0x04]);
+        assert_ne!(crc1, crc2);
+        assert_eq!(crc1.to_be_bytes(), FRAME1[7..11]);
+        assert_eq!(crc1, min.get_msg().ok().unwrap().crc);
+        assert_eq!(crc2, min.get_msg().ok().unwrap().crc);
+        assert_eq!(crc2, min.get_rx_frame_checksum());
+    }
+
+    #[test]
+    fn broadcast_interface() {
+        let sent1 = Rc::new(RefCell::new(Vec::new()));
+        let sent2 = Rc::new(RefCell::new(Vec::new()));
+        let broadcast = min::BroadcastInterface::new(vec![
+            Box::new(RecordingUart { tx_space: 128, sent: sent1.clone() }),
+            Box::new(RecordingUart { tx_space: 13, sent: sent2.clone() }),
+        ]);
+        assert_eq!(13, min::Interface::tx_space(&broadcast));
+        let mut min = min::Context::new(
+            String::from("min"),
+            &broadcast,
+            0,
+            false,
+        );
+
+        min.send_frame(1, &[0x01, 0x02], 2).ok().unwrap();
+        let frame = min::min_frame!(id = 1, payload = [0x01, 0x02]).to_vec();
+        assert_eq!(frame, *sent1.borrow());
+        assert_eq!(frame, *sent2.borrow());
+
+        // Sent only if it fits in every interface
+        assert!(min.send_frame(1, &[0x01, 0x02, 0x03], 3).is_err());
+        assert_eq!(12, sent1.borrow().len());
+    }
+
+    #[test]
+    fn tx_space_override() {
+        let uart = MinimalUart {
+            frames: Cell::new(0),
+        };
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        // Any frame fits by default
+        min.send_frame(1, &[0; 255], 255).ok().unwrap();
+        assert_eq!(1, uart.frames.get());
+
+        // 13 bytes: a 2-byte payload fits, not a 3-byte one
+        min.set_tx_space_override(Some(13));
+        min.send_frame(1, &[0x01, 0x02], 2).ok().unwrap();
+        assert!(min.send_frame(1, &[0x01, 0x02, 0x03], 3).is_err());
+        assert_eq!(2, uart.frames.get());
+
+        min.set_tx_space_override(None);
+        min.send_frame(1, &[0x01, 0x02, 0x03], 3).ok().unwrap();
+        assert_eq!(3, uart.frames.get());
+    }
+
+    #[test]
+    fn tx_space_u16_boundary() {
+        const FRAME: [u8; 11] = min::min_frame!(id = 1, payload = [0x01]);
+        // The whole u16 range free, not replenished
+        let uart = BoundedUart {
+            tx_space: Cell::new(u16::MAX),
+        };
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+        min.set_on_rx_frame(|msg| min::Reaction::QueueReply(msg.min_id, vec![0; 255]));
+
+        // 250 replies of 265 bytes in one pass, 247 fit
+        let mut received = Vec::new();
+        for _ in 0..250 {
+            received.extend_from_slice(&FRAME);
+        }
+        min.receive(&received);
+        assert_eq!(247 * 265, min.get_wire_bytes_sent_cnt());
+        assert_eq!(u16::MAX as u64 - 247 * 265, uart.tx_space.get() as u64);
+    }
+
+    #[test]
+    fn dedup_application_frames() {
+        const FRAME: [u8; 11] = min::min_frame!(id = 1, payload = [0x01]);
+        let uart = Uart::new(1024);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+        min.set_dedup_window(4);
+
+        // A repeated status message is not a duplicate
+        min.receive(&FRAME);
+        min.receive(&FRAME);
+        assert_eq!(2, min.drain_msgs().count());
+        assert_eq!(0, min.get_deduped_frame_cnt());
+    }
+
+    #[test]
+    fn reply_too_long() {
+        const FRAME: [u8; 11] = min::min_frame!(id = 1, payload = [0x01]);
+        let uart = BoundedUart {
+            tx_space: Cell::new(u16::MAX),
+        };
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+        min.set_on_rx_frame(|msg| min::Reaction::QueueReply(msg.min_id, vec![0; 256]));
+
+        // Not sent truncated to a wrong-length frame
+        min.receive(&FRAME);
+        assert_eq!(0, min.get_wire_bytes_sent_cnt());
+        assert_eq!(1, min.get_dropped_reply_cnt());
+    }
+
+    #[test]
+    fn header_len_round_trip() {
+        let sent = Rc::new(RefCell::new(Vec::new()));
+        let uart = RecordingUart { tx_space: 128, sent: sent.clone() };
+        let mut sender = min::Context::new(
+            String::from("sender"),
+            &uart,
+            0,
+            false,
+        );
+        sender.set_header_len(2);
+
+        let payload = [0xaa, 0x01, 0xaa, 0xaa];
+        sender.send_frame(1, &payload, payload.len() as u8).ok().unwrap();
+        let frame = sent.borrow().clone();
+        // Two header bytes, a stuff byte after every 0xaa of the frame
+        assert_eq!(&[0xaa, 0xaa, 0x01, 0x04, 0xaa, 0x55, 0x01, 0xaa, 0x55, 0xaa, 0x55], &frame[..11]);
+        assert_eq!(0x55, *frame.last().unwrap());
+        assert_eq!(frame, sender.encode_frame(1, &payload).ok().unwrap());
+
+        let receiver_uart = Uart::new(128);
+        let mut receiver = min::Context::new(
+            String::from("receiver"),
+            &receiver_uart,
+            0,
+            false,
+        );
+        receiver.set_header_len(2);
+        // Noise before the frame
+        receiver.receive(&[0x01, 0xaa, 0x02]);
+        receiver.receive(&frame);
+        let msg = receiver.get_msg().ok().unwrap();
+        assert_eq!(1, msg.min_id);
+        assert_eq!(payload.to_vec(), msg.buf);
+        assert!(receiver.get_msg().is_err());
+    }
 }
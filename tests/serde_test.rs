@@ -0,0 +1,70 @@
+#![cfg(all(feature = "std", feature = "serde"))]
+
+#[cfg(test)]
+mod tests {
+    extern crate min_rs as min;
+
+    struct Uart {
+        tx_space_avaliable: u16,
+    }
+
+    impl min::Interface for Uart {
+        fn tx_start(&self) {}
+        fn tx_finished(&self) {}
+        fn tx_space(&self) -> u16 {
+            self.tx_space_avaliable
+        }
+        fn tx_byte(&self, _port: u8, _byte: u8) {}
+    }
+
+    #[test]
+    fn msg_round_trip() {
+        let uart = Uart{
+            tx_space_avaliable: 128,
+        };
+        let payload: [u8; 19] = [
+            0xaa, 0xaa, 0xaa,   // SOF
+            0x00,   // ID/control
+            0x08,   // Length
+            0xaa, 0xaa, 0x55, 0xaa, 0x00, 0x00, 0x00, 0x00, 0x00,   // Data(including a stuff-byte 0x55)
+            0x38, 0x83, 0x8f, 0x82, // CRC checksum
+            0x55,   // EOF
+        ];
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        min.poll(&payload, payload.len() as u32);
+        let msg = min.get_msg().ok().unwrap();
+
+        let json = serde_json::to_string(&msg).unwrap();
+        let decoded: min::Msg = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(msg, decoded);
+        assert_eq!(vec![0xaa, 0xaa, 0xaa, 0x00, 0x00, 0x00, 0x00, 0x00], decoded.buf);
+    }
+
+    #[test]
+    fn stats_to_json() {
+        let uart = Uart{
+            tx_space_avaliable: 128,
+        };
+        let min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+
+        let json = serde_json::to_string(&min.get_transport_stats()).unwrap();
+        let decoded: min::TransportStats = serde_json::from_str(&json).unwrap();
+        assert_eq!(min.get_transport_stats(), decoded);
+
+        let json = serde_json::to_string(&min.health_check()).unwrap();
+        let decoded: min::LinkHealth = serde_json::from_str(&json).unwrap();
+        assert_eq!(min.health_check().in_flight, decoded.in_flight);
+    }
+}
@@ -0,0 +1,162 @@
+#![cfg(all(feature = "std", feature = "test-util"))]
+
+#[cfg(test)]
+mod tests {
+    extern crate min_rs as min;
+    use min::test_util::{assert_frame_eq, RecordingInterface};
+    use std::panic;
+
+    #[test]
+    fn record_sent_bytes() {
+        let uart = RecordingInterface::new(128);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        min.send(0, [0xaa, 0xaa, 0xaa, 0x00, 0x00, 0x00, 0x00, 0x01]).ok().unwrap();
+        assert_eq!(
+            vec![
+                0xaa, 0xaa, 0xaa,   // SOF
+                0x00,   // ID/control
+                0x08,   // Length
+                0xaa, 0xaa, 0x55, 0xaa, 0x00, 0x00, 0x00, 0x00, 0x01,   // Data(including a stuff-byte 0x55)
+                0x4f, 0x84, 0xbf, 0x14, // CRC checksum
+                0x55,   // EOF
+            ],
+            uart.sent_bytes()
+        );
+        let msgs = min.process(&uart.sent_bytes());
+        assert_eq!(1, msgs.len());
+
+        // Only the last frame is kept
+        min.send(0, [0x01]).ok().unwrap();
+        assert_eq!(11, uart.sent_bytes().len());
+
+        uart.set_tx_space(0);
+        assert!(min.send(0, [0x01]).is_err());
+        assert_eq!(11, uart.sent_bytes().len());
+    }
+
+    fn frame_diff(produced: &[u8], expected: &[u8]) -> String {
+        let produced = produced.to_vec();
+        let expected = expected.to_vec();
+        let error = panic::catch_unwind(move || assert_frame_eq(&produced, &expected)).unwrap_err();
+        error.downcast_ref::<String>().unwrap().lines().next().unwrap().to_string()
+    }
+
+    #[test]
+    fn frame_eq() {
+        let uart = RecordingInterface::new(128);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+        let expected = min.encode_frame(0x01, &[0xaa, 0xaa, 0x02]).ok().unwrap();
+
+        min.send(0x01, [0xaa, 0xaa, 0x02]).ok().unwrap();
+        assert_frame_eq(&uart.sent_bytes(), &expected);
+
+        // The stuff byte shifts the following bytes, the payload byte is still found
+        min.send(0x01, [0xaa, 0xaa, 0x03]).ok().unwrap();
+        assert_eq!("frames differ at payload byte 2: produced Some(03), expected Some(02)", frame_diff(&uart.sent_bytes(), &expected));
+
+        min.send(0x02, [0xaa, 0xaa, 0x02]).ok().unwrap();
+        assert!(frame_diff(&uart.sent_bytes(), &expected).starts_with("frames differ at ID/control"));
+
+        min.send(0x01, [0xaa, 0xaa]).ok().unwrap();
+        assert!(frame_diff(&uart.sent_bytes(), &expected).starts_with("frames differ at length"));
+
+        let mut crc_error = expected.clone();
+        crc_error[11] ^= 0x01;
+        assert!(frame_diff(&crc_error, &expected).starts_with("frames differ at CRC byte 2"));
+
+        let mut eof_error = expected.clone();
+        eof_error[13] = 0x00;
+        assert!(frame_diff(&eof_error, &expected).starts_with("frames differ at EOF"));
+
+        assert!(frame_diff(&expected[1..], &expected).starts_with("frames differ at header"));
+    }
+
+    #[test]
+    fn frame_eq_fields() {
+        let uart = RecordingInterface::new(128);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+
+        // Stuffed header bytes right after the header
+        let expected = min.encode_frame(0x01, &[0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0x02]).ok().unwrap();
+        let produced = min.encode_frame(0x01, &[0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0x03]).ok().unwrap();
+        assert_eq!("frames differ at payload byte 5: produced Some(03), expected Some(02)", frame_diff(&produced, &expected));
+
+        // The sequence numbers of transport frames are parsed
+        min.queue_frame(0x01, &[0x01], 1).unwrap();
+        min.queue_frame(0x01, &[0x01], 1).unwrap();
+        min.tick();
+        let first = uart.sent_bytes();
+        min.tick();
+        assert_eq!("frames differ at sequence number: produced Some(01), expected Some(00)", frame_diff(&uart.sent_bytes(), &first));
+    }
+
+    #[test]
+    fn inject_received_frames() {
+        let uart = RecordingInterface::new(128);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+
+        // In sequence: delivered and acknowledged
+        min.inject_frame(0x80 | 0x05, 0, &[0x01, 0x02]).unwrap();
+        let msg = min.get_msg().ok().unwrap();
+        assert_eq!(0x05, msg.min_id);
+        assert_eq!(vec![0x01, 0x02], msg.buf);
+        // ACK control byte, then Rn
+        assert_eq!(&[0xff, 0x01], &uart.sent_bytes()[3..5]);
+
+        // A duplicate is dropped
+        min.inject_frame(0x80 | 0x05, 0, &[0x01, 0x02]).unwrap();
+        assert!(min.get_msg().is_err());
+        assert_eq!(vec![min::FrameError::SequenceMismatch { seq: 0, rn: 1 }], min.drain_errors().collect::<Vec<min::FrameError>>());
+
+        // RESET
+        min.inject_frame(0xfe, 0, &[]).unwrap();
+        assert_eq!(1, min.get_reset_cnt());
+    }
+
+    #[test]
+    fn inject_ack_and_nack() {
+        let uart = RecordingInterface::new(128);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+        for i in 0..3 {
+            min.queue_frame(0x01, &[i], 1).unwrap();
+            min.tick();
+        }
+        assert_eq!(3, min.health_check().in_flight);
+
+        // Frame 0 acknowledged, frame 1 wanted again (NACK)
+        min.inject_frame(0xff, 1, &[2]).unwrap();
+        assert_eq!(2, min.health_check().in_flight);
+        // Sequence number of the retransmitted frame
+        assert_eq!(0x01, uart.sent_bytes()[4]);
+
+        // All acknowledged
+        min.inject_frame(0xff, 3, &[3]).unwrap();
+        assert_eq!(0, min.health_check().queued_frames);
+    }
+}
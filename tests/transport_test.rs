@@ -1,17 +1,32 @@
+#![cfg(feature = "std")]
 
 #[cfg(test)]
 mod tests {
     extern crate min_rs as min;
-    use std::cell::RefCell;
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
 
     struct Uart {
         tx_space_avaliable: u16,
         rx_buf: RefCell<[u8; 255]>,
         rx_buf_index: RefCell<u8>,
         loopback: bool,
+        link: Cell<bool>,
+        tx_frames: Cell<u32>,
     }
 
     impl Uart {
+        fn new(tx_space_avaliable: u16, loopback: bool) -> Self {
+            Uart {
+                tx_space_avaliable,
+                rx_buf: RefCell::new([0; 255]),
+                rx_buf_index: RefCell::new(0),
+                loopback,
+                link: Cell::new(true),
+                tx_frames: Cell::new(0),
+            }
+        }
+
         fn open(&self) {
             println!("Open uart.");
         }
@@ -56,17 +71,17 @@ mod tests {
             print!("[ ");
             let mut rx_buf_index = self.rx_buf_index.borrow_mut();
             *rx_buf_index = 0;
+            self.tx_frames.set(self.tx_frames.get() + 1);
+        }
+
+        fn link_up(&self) -> bool {
+            self.link.get()
         }
     }
 
     #[test]
     fn transport_receive_reset() {
-        let uart = Uart{
-            tx_space_avaliable: 128,
-            rx_buf: RefCell::new([0; 255]),
-            rx_buf_index: RefCell::new(0),
-            loopback: true,
-        };
+        let uart = Uart::new(128, true);
         let mut min = min::Context::new(
             String::from("min"),
             &uart,
@@ -84,15 +99,36 @@ mod tests {
         uart.close();
     }
 
+    #[test]
+    fn transport_reset_discarded_frames() {
+        let uart = Uart::new(128, false);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+
+        uart.open();
+
+        assert_eq!(0, min.reset_transport(false).ok().unwrap());
+        min.queue_frame(0x01, &[0x01], 1).unwrap();
+        min.queue_frame(0x02, &[0x02], 1).unwrap();
+        min.tick();
+        min.queue_frame(0x03, &[0x03], 1).unwrap();
+        assert_eq!(3, min.reset_transport(false).ok().unwrap());
+        assert_eq!(0, min.reset_transport(false).ok().unwrap());
+
+        min.disable_transport();
+        assert_eq!(Err(min::TransportError::NotSupported), min.reset_transport(false));
+
+        uart.close();
+    }
+
     #[test]
     fn transport_receive_spurious_ack() {
         let ack: [u8; 12] = [0xaa, 0xaa, 0xaa, 0xff, 0x02, 0x01, 0x02, 0x0b, 0xd0, 0x5d, 0xee, 0x55];
-        let uart = Uart{
-            tx_space_avaliable: 128,
-            rx_buf: RefCell::new([0; 255]),
-            rx_buf_index: RefCell::new(0),
-            loopback: false,
-        };
+        let uart = Uart::new(128, false);
         let mut min = min::Context::new(
             String::from("min"),
             &uart,
@@ -120,12 +156,7 @@ mod tests {
             0xe6, 0x98, 0x4f, 0xde,
             0x55
         ];
-        let uart = Uart{
-            tx_space_avaliable: 128,
-            rx_buf: RefCell::new([0; 255]),
-            rx_buf_index: RefCell::new(0),
-            loopback: false,
-        };
+        let uart = Uart::new(128, false);
         let mut min = min::Context::new(
             String::from("min"),
             &uart,
@@ -141,4 +172,2300 @@ mod tests {
 
         uart.close();
     }
+
+    #[test]
+    fn transport_resume_from_state() {
+        let uart = Uart::new(128, true);
+        let saved = min::TransportState {
+            sn_min: 5,
+            sn_max: 5,
+            rn: 3,
+        };
+        let state = min::TransportState::from_bytes(&saved.to_bytes()).unwrap();
+        let mut min = min::Context::from_transport_state(
+            String::from("min"),
+            &uart,
+            0,
+            state,
+        );
+
+        uart.open();
+
+        assert_eq!(min.get_transport_state(), saved);
+
+        min.queue_frame(0, &[0x01], 1).unwrap();
+        min.poll(&[0][0..0], 0);
+
+        // SOF(3 bytes), ID/control, sequence
+        assert_eq!(uart.rx_buf.borrow()[4], 5);
+        assert_eq!(min.get_transport_state().sn_max, 6);
+
+        uart.close();
+    }
+
+    #[test]
+    fn transport_link_down() {
+        let uart = Uart::new(128, true);
+        uart.link.set(false);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+
+        uart.open();
+
+        min.queue_frame(0, &[0x01], 1).unwrap();
+        min.poll(&[0][0..0], 0);
+
+        assert_eq!(uart.get_rx_data_len(), 0);
+
+        uart.link.set(true);
+        min.poll(&[0][0..0], 0);
+
+        assert_ne!(uart.get_rx_data_len(), 0);
+
+        uart.close();
+    }
+
+    #[test]
+    fn frame_wire_len() {
+        let uart = Uart::new(128, true);
+        let payload: [u8; 8] = [0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0x00, 0xaa, 0xaa];
+
+        uart.open();
+
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+        let expected = min.frame_wire_len(0, &payload);
+        min.send_frame(0, &payload, payload.len() as u8).unwrap_or(0);
+        assert_eq!(expected, uart.get_rx_data_len() as usize);
+
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+        let expected = min.frame_wire_len(0, &payload);
+        min.queue_frame(0, &payload, payload.len() as u8).unwrap();
+        min.poll(&[0][0..0], 0);
+        assert_eq!(expected, uart.get_rx_data_len() as usize);
+
+        uart.close();
+    }
+
+    #[test]
+    fn transport_piggyback_ack() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, true);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+        min1.set_piggyback_acks(true);
+        min2.set_piggyback_acks(true);
+
+        uart1.open();
+        uart2.open();
+
+        min1.queue_frame(1, &[0x01, 0x02], 2).unwrap();
+        min1.poll(&[0][0..0], 0);
+        assert_eq!(1, uart1.tx_frames.get());
+
+        // min2 has a frame of its own to send: the ACK rides on it
+        min2.queue_frame(2, &[0x03], 1).unwrap();
+        min2.poll(&uart1.rx_buf.borrow()[0..255], uart1.get_rx_data_len() as u32);
+        assert_eq!(1, uart2.tx_frames.get());
+        // SOF(3 bytes), ID/control, sequence, piggybacked Rn
+        assert_eq!(0x82, uart2.rx_buf.borrow()[3]);
+        assert_eq!(1, uart2.rx_buf.borrow()[5]);
+        assert_eq!(Some(1), min2.get_msg().ok().map(|msg| msg.min_id));
+
+        min1.poll(&uart2.rx_buf.borrow()[0..255], uart2.get_rx_data_len() as u32);
+        assert_eq!(1, min1.get_transport_state().sn_min);
+        assert_eq!(Some(2), min1.get_msg().ok().map(|msg| msg.min_id));
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn transport_health_check() {
+        let uart = Uart::new(128, false);
+        let bad_crc: [u8; 12] = [0xaa, 0xaa, 0xaa, 0xff, 0x02, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x55];
+        let clock = min::MockClock::new(0);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+        min.set_clock(&clock);
+
+        uart.open();
+
+        let health = min.health_check();
+        assert!(health.link_up);
+        assert_eq!(None, health.ms_since_last_rx);
+        assert_eq!(0.0, health.crc_error_rate);
+
+        min.queue_frame(0, &[0x01], 1).unwrap();
+        min.queue_frame(0, &[0x02], 1).unwrap();
+        min.poll(&bad_crc[..], bad_crc.len() as u32);
+
+        let health = min.health_check();
+        assert_eq!(1, health.in_flight);
+        assert_eq!(2, health.queued_frames);
+        assert_eq!(1.0, health.crc_error_rate);
+        assert!(!health.tx_stalled);
+
+        clock.advance(1000);
+        assert!(min.health_check().tx_stalled);
+
+        uart.close();
+    }
+
+    #[test]
+    fn stuff_consecutive_header_bytes() {
+        let uart = Uart::new(128, true);
+        let payload: [u8; 7] = [0xaa; 7];
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        uart.open();
+
+        min.send_frame(0, &payload, payload.len() as u8).unwrap_or(0);
+
+        // SOF(3 bytes), ID/control, length, then a stuff byte after every second 0xaa
+        assert_eq!(
+            [0xaa, 0xaa, 0x55, 0xaa, 0xaa, 0x55, 0xaa, 0xaa, 0x55, 0xaa],
+            uart.rx_buf.borrow()[5..15]
+        );
+        assert_eq!(min.frame_wire_len(0, &payload), uart.get_rx_data_len() as usize);
+
+        let mut received = [0_u8; 255];
+        let len = uart.get_rx_data_len() as usize;
+        received[..len].copy_from_slice(&uart.rx_buf.borrow()[..len]);
+        min.poll(&received[..len], len as u32);
+        assert_eq!(Some(payload.to_vec()), min.get_msg().ok().map(|msg| msg.buf));
+
+        uart.close();
+    }
+
+    #[test]
+    fn spare_control_bit() {
+        let uart = Uart::new(128, true);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        uart.open();
+
+        min.send_frame_control(min::SPARE_CONTROL_BIT | 5, &[0x01], 1).unwrap_or(0);
+        min.poll(&uart.rx_buf.borrow()[0..255], uart.get_rx_data_len() as u32);
+        let msg = min.get_msg().ok().unwrap();
+        assert_eq!(5, msg.min_id);
+        assert_eq!(0x45, msg.control_byte);
+
+        // The transport bit can't be set this way
+        min.send_frame_control(0x80 | 5, &[0x01], 1).unwrap_or(0);
+        assert_eq!(0x05, uart.rx_buf.borrow()[3]);
+
+        min.send_frame(5, &[0x01], 1).unwrap_or(0);
+        min.poll(&uart.rx_buf.borrow()[0..255], uart.get_rx_data_len() as u32);
+        assert_eq!(0x05, min.get_msg().ok().unwrap().control_byte);
+
+        uart.close();
+    }
+
+    #[test]
+    fn transport_echo_reply() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, true);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+        min2.set_on_rx_frame(|msg| min::Reaction::QueueReply(msg.min_id, msg.buf.clone()));
+
+        uart1.open();
+        uart2.open();
+
+        min1.queue_frame(3, &[0x01, 0x02], 2).unwrap();
+        min1.poll(&[0][0..0], 0);
+        min2.poll(&uart1.rx_buf.borrow()[0..255], uart1.get_rx_data_len() as u32);
+
+        // ACK, then the reply
+        assert_eq!(2, uart2.tx_frames.get());
+        assert_eq!(Some(vec![0x01, 0x02]), min2.get_msg().ok().map(|msg| msg.buf));
+
+        let mut received = [0_u8; 255];
+        let len = uart2.get_rx_data_len() as usize;
+        received[..len].copy_from_slice(&uart2.rx_buf.borrow()[..len]);
+        min1.poll(&received[..len], len as u32);
+        let reply = min1.get_msg().ok().unwrap();
+        assert_eq!(3, reply.min_id);
+        assert_eq!(vec![0x01, 0x02], reply.buf);
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn transport_reply_fifo_full() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, true);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+        min2.set_on_rx_frame(|msg| min::Reaction::QueueReply(msg.min_id, msg.buf.clone()));
+
+        uart1.open();
+        uart2.open();
+
+        // No room left for the reply
+        for _ in 0..31 {
+            min2.queue_frame(4, &[0x05], 1).ok().unwrap();
+        }
+        min1.queue_frame(3, &[0x01, 0x02], 2).ok().unwrap();
+        min1.poll(&[0][0..0], 0);
+        min2.poll(&uart1.rx_buf.borrow()[0..255], uart1.get_rx_data_len() as u32);
+
+        assert_eq!(Some(vec![0x01, 0x02]), min2.get_msg().ok().map(|msg| msg.buf));
+        assert_eq!(1, min2.get_dropped_reply_cnt());
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn transport_enable_disable() {
+        let uart = Uart::new(128, true);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        uart.open();
+
+        assert!(!min.transport_enabled());
+        assert!(min.queue_frame(0, &[0x01], 1).is_err());
+
+        min.enable_transport();
+        assert!(min.transport_enabled());
+        min.queue_frame(0, &[0x01], 1).unwrap();
+        assert_eq!(1, min.health_check().queued_frames);
+
+        min.disable_transport();
+        assert!(!min.transport_enabled());
+        assert_eq!(0, min.health_check().queued_frames);
+        min.poll(&[0][0..0], 0);
+        assert_eq!(0, uart.tx_frames.get());
+
+        uart.close();
+    }
+
+    #[test]
+    fn transport_reuse_acked_frames() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, true);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+
+        uart1.open();
+        uart2.open();
+
+        for payload in [vec![0x01, 0x02, 0x03, 0x04], vec![0x09]] {
+            min1.queue_frame(0, &payload, payload.len() as u8).unwrap();
+            min1.poll(&[0][0..0], 0);
+            min2.poll(&uart1.rx_buf.borrow()[0..255], uart1.get_rx_data_len() as u32);
+            assert_eq!(Some(payload), min2.get_msg().ok().map(|msg| msg.buf));
+            // ACK
+            min1.poll(&uart2.rx_buf.borrow()[0..255], uart2.get_rx_data_len() as u32);
+            assert_eq!(0, min1.health_check().queued_frames);
+        }
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn transport_next_unsent() {
+        let uart = Uart::new(128, false);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+
+        uart.open();
+
+        assert_eq!(None, min.next_unsent());
+
+        min.queue_frame(1, &[0x01], 1).unwrap();
+        min.queue_frame(2, &[0x01, 0x02, 0x03], 3).unwrap();
+        assert_eq!(Some((1, 1)), min.next_unsent());
+
+        min.poll(&[0][0..0], 0);
+        assert_eq!(Some((2, 3)), min.next_unsent());
+
+        min.poll(&[0][0..0], 0);
+        assert_eq!(None, min.next_unsent());
+
+        uart.close();
+    }
+
+    #[test]
+    fn id_handlers() {
+        let uart = Uart::new(128, true);
+        let received: RefCell<Vec<(u8, u8)>> = RefCell::new(Vec::new());
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+        min.on_id(1, |msg| received.borrow_mut().push((1, msg.buf[0])));
+        min.on_id(2, |msg| received.borrow_mut().push((2, msg.buf[0])));
+
+        uart.open();
+
+        for (id, data) in [(1, 0x10), (3, 0x30), (2, 0x20), (1, 0x11)] {
+            min.send_frame(id, &[data], 1).unwrap_or(0);
+            min.poll(&uart.rx_buf.borrow()[0..255], uart.get_rx_data_len() as u32);
+        }
+
+        uart.close();
+
+        assert_eq!(vec![(1, 0x10), (2, 0x20), (1, 0x11)], *received.borrow());
+        let msg = min.get_msg().ok().unwrap();
+        assert_eq!(3, msg.min_id);
+        assert!(min.get_msg().is_err());
+    }
+
+    #[test]
+    fn transport_encode_ack() {
+        let ack: [u8; 12] = [0xaa, 0xaa, 0xaa, 0xff, 0x02, 0x01, 0x02, 0x0b, 0xd0, 0x5d, 0xee, 0x55];
+        let uart = Uart::new(128, false);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+
+        uart.open();
+
+        assert_eq!(&ack[..], &min.encode_ack(2)[..]);
+        assert_eq!(0, uart.tx_frames.get());
+        assert_eq!(0, uart.get_rx_data_len());
+
+        uart.close();
+    }
+
+    #[test]
+    fn transport_ack_throttle() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, false);
+        let clock = min::MockClock::new(0);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+        min1.set_clock(&clock);
+        min2.set_clock(&clock);
+        // 10 ms more per queued message
+        min2.set_ack_throttle(Some(250 + 1280));
+
+        uart1.open();
+        uart2.open();
+
+        // Frames sent by min1, never acknowledged
+        let mut frames: Vec<Vec<u8>> = Vec::new();
+        for i in 0..3 {
+            min1.queue_frame(0, &[i], 1).unwrap();
+            min1.tick();
+            frames.push(uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize].to_vec());
+        }
+
+        assert_eq!(250, min2.get_ack_delay_ms());
+
+        // The queue is empty: the frame is acknowledged right away
+        min2.receive(&frames[0]);
+        assert_eq!(1, uart2.tx_frames.get());
+        assert_eq!(260, min2.get_ack_delay_ms());
+
+        // A message is waiting: the ACK is delayed
+        min2.poll(&frames[1], frames[1].len() as u32);
+        assert_eq!(1, uart2.tx_frames.get());
+        assert_eq!(270, min2.get_ack_delay_ms());
+        clock.advance(270);
+        min2.tick();
+        assert_eq!(1, uart2.tx_frames.get());
+        clock.advance(1);
+        min2.tick();
+        assert_eq!(2, uart2.tx_frames.get());
+
+        // The ACKs space out as the queue grows
+        min2.poll(&frames[2], frames[2].len() as u32);
+        assert_eq!(280, min2.get_ack_delay_ms());
+        clock.advance(280);
+        min2.tick();
+        assert_eq!(2, uart2.tx_frames.get());
+        clock.advance(1);
+        min2.tick();
+        assert_eq!(3, uart2.tx_frames.get());
+
+        // Scaled to the bound of the queue when there is one
+        min2.set_msg_queue_capacity(Some(4), min::OverflowPolicy::DropNewest);
+        assert_eq!(250 + 1280 * 3 / 4, min2.get_ack_delay_ms());
+
+        // Back to normal once the messages are taken
+        min2.drain_msgs();
+        assert_eq!(250, min2.get_ack_delay_ms());
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn transport_handoff() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, false);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut peer = min::Context::new(
+            String::from("peer"),
+            &uart2,
+            0,
+            true,
+        );
+
+        uart1.open();
+        uart2.open();
+
+        min1.queue_frame(1, &[0x01], 1).unwrap();
+        min1.queue_frame(2, &[0x02, 0x02], 2).unwrap();
+        min1.tick();
+        // The first frame is received but its ACK never gets back
+        peer.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+
+        let (state, pending) = min1.extract_state();
+        assert_eq!(min::TransportState { sn_min: 0, sn_max: 1, rn: 0 }, state);
+        assert_eq!(vec![(1, vec![0x01]), (2, vec![0x02, 0x02])], pending);
+
+        let mut min2 = min::Context::resume(
+            String::from("min2"),
+            &uart1,
+            0,
+            state,
+            pending,
+        ).ok().unwrap();
+        // The in-flight frame is sent again and dropped as a duplicate
+        min2.tick();
+        peer.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+        assert_eq!(1, peer.get_drop_cnt());
+        min2.tick();
+        peer.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+
+        let ids: Vec<u8> = peer.drain_msgs().map(|msg| msg.min_id).collect();
+        assert_eq!(vec![1, 2], ids);
+        assert_eq!(0, peer.get_reset_cnt());
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn crc_final_xor() {
+        let uart = Uart::new(128, true);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        uart.open();
+
+        min.send(0, [0x01]).ok().unwrap();
+        let inverted = uart.rx_buf.borrow()[0..uart.get_rx_data_len() as usize].to_vec();
+        min.set_crc_final_xor(0);
+        min.send(0, [0x01]).ok().unwrap();
+        let frame = uart.rx_buf.borrow()[0..uart.get_rx_data_len() as usize].to_vec();
+        // SOF(3 bytes), ID/control, length, payload, then the CRC
+        for i in 6..10 {
+            assert_eq!(!inverted[i], frame[i]);
+        }
+        assert_eq!(1, min.process(&frame).len());
+
+        // A peer inverting the CRC rejects the frame
+        min.set_crc_final_xor(0xffffffff);
+        assert!(min.process(&frame).is_empty());
+        assert_eq!(1, min.get_crc_error_cnt());
+
+        uart.close();
+    }
+
+    #[test]
+    fn transport_can_queue() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, true);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+
+        uart1.open();
+        uart2.open();
+
+        let mut queued = 0;
+        while min1.can_queue() {
+            min1.queue_frame(0, &[queued], 1).unwrap();
+            min1.tick();
+            min2.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+            queued += 1;
+        }
+        assert_eq!(16, queued);
+        assert_eq!(16, min1.health_check().in_flight);
+
+        // The ACK of the last frame acknowledges them all
+        min1.receive(&uart2.rx_buf.borrow()[0..uart2.get_rx_data_len() as usize]);
+        assert_eq!(0, min1.health_check().queued_frames);
+        assert!(min1.can_queue());
+
+        min1.disable_transport();
+        assert!(!min1.can_queue());
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn crc_covers_eof() {
+        let uart = Uart::new(128, true);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        uart.open();
+
+        min.set_crc_covers_eof(true);
+        let payload = [0xaa, 0xaa, 0x01];
+        min.send(0, payload).ok().unwrap();
+        let frame = uart.rx_buf.borrow()[0..uart.get_rx_data_len() as usize].to_vec();
+        assert_eq!(frame.len(), min.frame_wire_len(0, &payload));
+        assert_eq!(Some(0x55), frame.last().copied());
+        let msgs = min.process(&frame);
+        assert_eq!(1, msgs.len());
+        assert_eq!(payload.to_vec(), msgs[0].buf);
+
+        // Rejected by the usual framing
+        min.set_crc_covers_eof(false);
+        assert!(min.process(&frame).is_empty());
+        assert_eq!(1, min.get_crc_error_cnt());
+
+        uart.close();
+    }
+
+    #[test]
+    fn transport_tick_report() {
+        let uart = Uart::new(128, false);
+        // Out of sequence frame from the other side, keeps the remote active
+        let frame: [u8; 19] = [
+            0xaa, 0xaa, 0xaa,
+            0x80,
+            0x01,   // Sequence
+            0x08,
+            0xbb, 0xbb, 0xbb, 0x00, 0x00, 0x00, 0x00, 0x01,
+            0xe6, 0x98, 0x4f, 0xde,
+            0x55
+        ];
+        let clock = min::MockClock::new(0);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+        min.set_clock(&clock);
+
+        uart.open();
+
+        let sent = min::TickReport { acks_sent: 0, frames_sent: 1, frames_retransmitted: 0 };
+        min.queue_frame(0, &[0x01], 1).unwrap();
+        min.queue_frame(0, &[0x02], 1).unwrap();
+        assert_eq!(sent, min.tick());
+        assert_eq!(sent, min.tick());
+        assert_eq!(min::TickReport::default(), min.tick());
+
+        clock.advance(1000);
+        min.receive(&frame);
+        let report = min.tick();
+        assert_eq!(min::TickReport { acks_sent: 1, frames_sent: 0, frames_retransmitted: 1 }, report);
+        let report = min.tick();
+        assert_eq!(min::TickReport { acks_sent: 0, frames_sent: 0, frames_retransmitted: 1 }, report);
+        clock.advance(1);
+        assert_eq!(min::TickReport::default(), min.tick());
+
+        clock.advance(250);
+        let report = min.tick();
+        assert_eq!(min::TickReport { acks_sent: 1, frames_sent: 0, frames_retransmitted: 0 }, report);
+        assert_eq!(6, uart.tx_frames.get());
+
+        uart.close();
+    }
+
+    // Out of sequence frame from the other side, keeps the remote active
+    const OUT_OF_SEQUENCE_FRAME: [u8; 19] = [
+        0xaa, 0xaa, 0xaa,
+        0x80,
+        0x01,   // Sequence
+        0x08,
+        0xbb, 0xbb, 0xbb, 0x00, 0x00, 0x00, 0x00, 0x01,
+        0xe6, 0x98, 0x4f, 0xde,
+        0x55
+    ];
+
+    /// Ticks every ms up to `until`, returns when the first retransmission happened.
+    fn first_retransmit_ms(min: &mut min::Context<Uart>, clock: &min::MockClock, until: u128) -> Option<u128> {
+        use min::Clock;
+        while clock.now_ms() <= until {
+            min.receive(&OUT_OF_SEQUENCE_FRAME);
+            if min.tick().frames_retransmitted > 0 {
+                return Some(clock.now_ms());
+            }
+            clock.advance(1);
+        }
+        None
+    }
+
+    #[test]
+    fn transport_drain_errors() {
+        let uart = Uart::new(128, false);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+
+        min.receive(&OUT_OF_SEQUENCE_FRAME);
+        assert_eq!(vec![min::FrameError::SequenceMismatch { seq: 1, rn: 0 }], min.drain_errors().collect::<Vec<min::FrameError>>());
+    }
+
+    #[test]
+    fn transport_retransmit_backoff() {
+        let uart = Uart::new(128, false);
+        let clock = min::MockClock::new(0);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+        min.set_clock(&clock);
+        min.set_retransmit_backoff(Some(4000));
+
+        uart.open();
+
+        min.queue_frame(0, &[0x01], 1).unwrap();
+        assert_eq!(1, min.tick().frames_sent);
+        clock.advance(1);
+
+        // 1 s, 2 s, then 4 s between the retransmissions
+        assert_eq!(Some(1000), first_retransmit_ms(&mut min, &clock, 10000));
+        clock.advance(1);
+        assert_eq!(Some(3000), first_retransmit_ms(&mut min, &clock, 10000));
+        clock.advance(1);
+        assert_eq!(Some(7000), first_retransmit_ms(&mut min, &clock, 20000));
+        clock.advance(1);
+        assert_eq!(Some(11000), first_retransmit_ms(&mut min, &clock, 20000));
+
+        uart.close();
+    }
+
+    #[test]
+    fn transport_retransmit_jitter() {
+        let uart = Uart::new(128, false);
+
+        uart.open();
+
+        let mut retransmits: Vec<u128> = Vec::new();
+        for _ in 0..2 {
+            let clock = min::MockClock::new(0);
+            let mut min = min::Context::new(
+                String::from("min"),
+                &uart,
+                0,
+                true,
+            );
+            min.set_clock(&clock);
+            min.set_retransmit_jitter(100, 42);
+
+            min.queue_frame(0, &[0x01], 1).unwrap();
+            min.tick();
+            clock.advance(1);
+            let at = first_retransmit_ms(&mut min, &clock, 2000).unwrap();
+            assert!((1000..=1100).contains(&at));
+            retransmits.push(at);
+        }
+        // Same seed, same delay
+        assert_eq!(retransmits[0], retransmits[1]);
+
+        uart.close();
+    }
+
+    // Always draws the same number
+    struct FixedRng(u32);
+
+    impl min::Rng for FixedRng {
+        fn next_u32(&mut self) -> u32 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn transport_custom_rng() {
+        use min::Rng;
+        let uart = Uart::new(128, false);
+        let clock = min::MockClock::new(0);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+        min.set_clock(&clock);
+        min.set_retransmit_jitter(100, 42);
+        min.set_rng(FixedRng(1037));
+
+        min.queue_frame(0, &[0x01], 1).unwrap();
+        min.tick();
+        clock.advance(1);
+        // 1037 % 101
+        assert_eq!(Some(1027), first_retransmit_ms(&mut min, &clock, 2000));
+
+        // Same seed, same sequence
+        let mut rng1 = min::XorShift32::new(42);
+        let mut rng2 = min::XorShift32::new(42);
+        let sequence: Vec<u32> = (0..4).map(|_| rng1.next_u32()).collect();
+        assert_eq!(sequence, (0..4).map(|_| rng2.next_u32()).collect::<Vec<u32>>());
+        assert_eq!(11355432, sequence[0]);
+    }
+
+    #[test]
+    fn transport_set_timeouts() {
+        let uart = Uart::new(128, false);
+        let clock = min::MockClock::new(0);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+        min.set_clock(&clock);
+
+        min.queue_frame(0, &[0x01], 1).unwrap();
+        min.tick();
+        clock.advance(1);
+        assert_eq!(Some(1000), first_retransmit_ms(&mut min, &clock, 2000));
+
+        // ACKs must be resent before frames
+        assert_eq!(Err(min::ConfigError::AckTimeoutOverRetransmit(400, 400)), min.set_timeouts(400, 400, 500));
+        assert_eq!(1000, min.transport_config().retransmit_timeout_ms);
+
+        min.set_timeouts(100, 400, 500).unwrap();
+        assert_eq!(400, min.transport_config().retransmit_timeout_ms);
+        // Resent at 1000 with the old timeout, the new one applies from the next send
+        clock.advance(1);
+        assert_eq!(Some(2000), first_retransmit_ms(&mut min, &clock, 3000));
+        clock.advance(1);
+        assert_eq!(Some(2400), first_retransmit_ms(&mut min, &clock, 3000));
+    }
+
+    #[test]
+    fn transport_send_oob() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, false);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+
+        uart1.open();
+        uart2.open();
+
+        min1.queue_frame(1, &[0x01], 1).unwrap();
+        min1.queue_frame(2, &[0x02], 1).unwrap();
+        min1.tick();
+        min2.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+        min1.send_oob(0x3f, [0xff]).ok().unwrap();
+        min2.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+        min1.tick();
+        min2.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+
+        let msgs: Vec<(u8, u8)> = min2.drain_msgs().map(|msg| (msg.min_id, msg.control_byte)).collect();
+        assert_eq!(vec![(1, 0x81), (0x3f, 0x3f), (2, 0x82)], msgs);
+        // The OOB frame is not part of the transport
+        assert_eq!(2, min2.get_transport_state().rn);
+        assert_eq!(2, min1.health_check().queued_frames);
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn transport_drain_acked() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, true);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+
+        uart1.open();
+        uart2.open();
+
+        for id in 1..4 {
+            min1.queue_frame(id, &[id], 1).unwrap();
+        }
+        for _ in 0..2 {
+            min1.tick();
+            min2.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+            min1.receive(&uart2.rx_buf.borrow()[0..uart2.get_rx_data_len() as usize]);
+        }
+
+        let delivered: Vec<u8> = min2.drain_msgs().map(|msg| msg.min_id).collect();
+        let acked: Vec<(u8, u8)> = min1.drain_acked().collect();
+        assert_eq!(vec![1, 2], delivered);
+        assert_eq!(vec![(1, 0), (2, 1)], acked);
+        assert_eq!(0, min1.drain_acked().count());
+
+        min1.tick();
+        min2.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+        min1.receive(&uart2.rx_buf.borrow()[0..uart2.get_rx_data_len() as usize]);
+        assert_eq!(vec![(3, 2)], min1.drain_acked().collect::<Vec<(u8, u8)>>());
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn transport_ping() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, true);
+        let clock = min::MockClock::new(0);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+        min1.set_clock(&clock);
+        min2.set_clock(&clock);
+
+        uart1.open();
+        uart2.open();
+
+        let token = min1.ping().ok().unwrap();
+        min1.tick();
+        assert_eq!(None, min1.take_rtt(token));
+
+        clock.advance(30);
+        min2.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+        min1.receive(&uart2.rx_buf.borrow()[0..uart2.get_rx_data_len() as usize]);
+        assert_eq!(Some(30), min1.take_rtt(token));
+        assert_eq!(None, min1.take_rtt(token));
+
+        let msgs: Vec<min::Msg> = min2.drain_msgs().collect();
+        assert_eq!(1, msgs.len());
+        assert_eq!(min::PING_ID, msgs[0].min_id);
+        assert_eq!(0, msgs[0].len);
+
+        let mut min3 = min::Context::new(
+            String::from("min3"),
+            &uart1,
+            0,
+            false,
+        );
+        assert!(min3.ping().is_err());
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn transport_synchronized() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, true);
+        let clock = min::MockClock::new(0);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+        min1.set_clock(&clock);
+        min2.set_clock(&clock);
+
+        uart1.open();
+        uart2.open();
+
+        assert!(!min1.is_synchronized());
+        assert!(!min2.is_synchronized());
+
+        min1.queue_frame(0, &[0x01], 1).unwrap();
+        min1.tick();
+        // The frame makes the receiver synchronized, its ACK the sender
+        min2.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+        assert!(min2.is_synchronized());
+        assert!(!min1.is_synchronized());
+        min1.receive(&uart2.rx_buf.borrow()[0..uart2.get_rx_data_len() as usize]);
+        assert!(min1.is_synchronized());
+
+        min1.reset_transport(false).unwrap();
+        assert!(!min1.is_synchronized());
+
+        let min3 = min::Context::new(
+            String::from("min3"),
+            &uart1,
+            0,
+            false,
+        );
+        assert!(!min3.is_synchronized());
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn transport_max_ack_latency() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, true);
+        let clock = min::MockClock::new(0);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+        min1.set_clock(&clock);
+        min2.set_clock(&clock);
+
+        uart1.open();
+        uart2.open();
+
+        assert_eq!(None, min1.max_ack_latency_ms());
+        for latency in [40, 90, 20].iter() {
+            min1.queue_frame(0, &[0x01], 1).unwrap();
+            min1.tick();
+            clock.advance(*latency);
+            min2.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+            min1.receive(&uart2.rx_buf.borrow()[0..uart2.get_rx_data_len() as usize]);
+        }
+        assert_eq!(Some(90), min1.max_ack_latency_ms());
+        assert_eq!(3, min2.drain_msgs().count());
+
+        min1.reset_max_ack_latency();
+        assert_eq!(None, min1.max_ack_latency_ms());
+        min1.queue_frame(0, &[0x01], 1).unwrap();
+        min1.tick();
+        clock.advance(10);
+        min2.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+        min1.receive(&uart2.rx_buf.borrow()[0..uart2.get_rx_data_len() as usize]);
+        assert_eq!(Some(10), min1.max_ack_latency_ms());
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn transport_listen_only() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, true);
+        let clock = min::MockClock::new(0);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut sniffer = min::Context::new(
+            String::from("sniffer"),
+            &uart2,
+            0,
+            true,
+        );
+        min1.set_clock(&clock);
+        sniffer.set_clock(&clock);
+        sniffer.set_listen_only(true);
+
+        uart1.open();
+        uart2.open();
+
+        let mut first_frame = Vec::new();
+        for i in 0..3 {
+            min1.queue_frame(0, &[i], 1).unwrap();
+            min1.tick();
+            let sent = uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize].to_vec();
+            sniffer.receive(&sent);
+            if i == 0 {
+                first_frame = sent;
+            }
+            clock.advance(10);
+            sniffer.tick();
+        }
+        // A retransmission is dropped as a duplicate
+        sniffer.receive(&first_frame);
+        let payloads: Vec<Vec<u8>> = sniffer.drain_msgs().map(|msg| msg.buf).collect();
+        assert_eq!(vec![vec![0], vec![1], vec![2]], payloads);
+        assert_eq!(1, sniffer.get_transport_stats().sequence_mismatch_drop);
+
+        // Nothing goes out, whatever the application does
+        sniffer.send_frame(1, &[0x01], 1).ok().unwrap();
+        sniffer.queue_frame(1, &[0x01], 1).unwrap();
+        for _ in 0..10 {
+            clock.advance(1000);
+            sniffer.tick();
+        }
+        sniffer.reset_transport(true).unwrap();
+        assert_eq!(0, uart2.tx_frames.get());
+        assert_eq!(0, uart2.get_rx_data_len());
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn transport_dedup() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, false);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+        min2.set_dedup_window(4);
+
+        uart1.open();
+        uart2.open();
+
+        min1.queue_frame(0, &[0x01], 1).unwrap();
+        min1.tick();
+        let frame = uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize].to_vec();
+        min1.reset_transport(true).unwrap();
+        let reset = uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize].to_vec();
+
+        // The frame replayed after a reset is in sequence again, but a duplicate
+        min2.receive(&frame);
+        min2.receive(&reset);
+        min2.receive(&frame);
+        assert_eq!(1, min2.drain_msgs().count());
+        assert_eq!(1, min2.get_deduped_frame_cnt());
+
+        // Without the window it is delivered twice
+        min2.set_dedup_window(0);
+        min2.receive(&reset);
+        min2.receive(&frame);
+        assert_eq!(1, min2.drain_msgs().count());
+        assert_eq!(1, min2.get_deduped_frame_cnt());
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn transport_dedup_filtered() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, false);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+        min2.set_dedup_window(4);
+        // Vetoes the first frame only
+        let mut vetoed = false;
+        min2.set_accept_filter(move |_, _, _| std::mem::replace(&mut vetoed, true));
+
+        uart1.open();
+        uart2.open();
+
+        min1.queue_frame(0, &[0x01], 1).unwrap();
+        min1.tick();
+        let frame = uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize].to_vec();
+        min1.reset_transport(true).unwrap();
+        let reset = uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize].to_vec();
+
+        // The copy replayed after a reset is not a duplicate of the filtered one
+        min2.receive(&frame);
+        assert_eq!(0, min2.drain_msgs().count());
+        min2.receive(&reset);
+        min2.receive(&frame);
+        assert_eq!(1, min2.drain_msgs().count());
+        assert_eq!(1, min2.get_filtered_drop_cnt());
+        assert_eq!(0, min2.get_deduped_frame_cnt());
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn transport_inspect() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, false);
+        let clock = min::MockClock::new(0);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+        min1.set_clock(&clock);
+        min2.set_clock(&clock);
+
+        uart1.open();
+        uart2.open();
+
+        for i in 0..2 {
+            min1.queue_frame(i, &[i], 1).unwrap();
+            min1.tick();
+            min2.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+        }
+        min2.queue_frame(5, &[0x01, 0x02], 2).unwrap();
+        min2.queue_frame(6, &[], 0).unwrap();
+        min2.tick();
+        // Start of a frame
+        min2.receive(&[0xaa, 0xaa, 0xaa]);
+
+        let view = min2.inspect();
+        let ids: Vec<u8> = view.msgs().map(|msg| msg.min_id).collect();
+        assert_eq!(vec![0, 1], ids);
+        assert_eq!(vec![
+            min::FrameInfo { min_id: 5, seq: 0, payload_len: 2, in_flight: true, retransmits: 0 },
+            min::FrameInfo { min_id: 6, seq: 0, payload_len: 0, in_flight: false, retransmits: 0 },
+        ], view.frames().collect::<Vec<min::FrameInfo>>());
+        assert_eq!(min::RxState::ReceivingIdControl, view.rx_state());
+        assert_eq!(min::TransportState { sn_min: 0, sn_max: 1, rn: 2 }, view.transport_state());
+        assert_eq!(2, view.transport_stats().n_frames_max);
+        assert_eq!(2, view.health().pending_msgs);
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn transport_config() {
+        let uart = Uart::new(128, false);
+
+        assert_eq!(Ok(()), min::TransportConfig::default().validate());
+
+        let config = min::TransportConfig {
+            window_size: 8,
+            fifo_capacity: 4,
+            ..Default::default()
+        };
+        assert_eq!(Err(min::ConfigError::WindowOverFifo(8, 4)), config.validate());
+
+        let config = min::TransportConfig {
+            ack_timeout_ms: 1000,
+            retransmit_timeout_ms: 1000,
+            ..Default::default()
+        };
+        assert_eq!(Err(min::ConfigError::AckTimeoutOverRetransmit(1000, 1000)), config.validate());
+        assert_eq!("ACK timeout 1000 ms not below the retransmit timeout 1000 ms", config.validate().unwrap_err().to_string());
+
+        let config = min::TransportConfig {
+            window_size: 0,
+            ..Default::default()
+        };
+        assert_eq!(Err(min::ConfigError::WindowSize(0)), config.validate());
+
+        let config = min::TransportConfig {
+            fifo_capacity: 32,
+            ..Default::default()
+        };
+        assert_eq!(Err(min::ConfigError::FifoCapacity(32)), config.validate());
+
+        let config = min::TransportConfig {
+            window_size: 17,
+            fifo_capacity: 31,
+            ..Default::default()
+        };
+        assert!(min::Context::new(String::from("min"), &uart, 0, true).with_transport_config(config).is_err());
+
+        let config = min::TransportConfig {
+            window_size: 4,
+            fifo_capacity: 8,
+            ..Default::default()
+        };
+        let mut min = min::Context::new(String::from("min"), &uart, 0, true)
+            .with_transport_config(config)
+            .ok()
+            .unwrap();
+        assert_eq!(config, min.transport_config());
+        assert_eq!(4, min.max_window());
+        assert_eq!(8, min.fifo_capacity());
+
+        let mut queued = 0;
+        while min.can_queue() {
+            min.queue_frame(0, &[queued], 1).unwrap();
+            min.tick();
+            queued += 1;
+        }
+        assert_eq!(4, queued);
+        assert_eq!(4, min.health_check().in_flight);
+    }
+
+    #[test]
+    fn transport_fifo_overflow() {
+        let uart = Uart::new(128, false);
+        let config = min::TransportConfig {
+            window_size: 1,
+            fifo_capacity: 1,
+            ..Default::default()
+        };
+        let mut min = min::Context::new(String::from("min"), &uart, 0, true)
+            .with_transport_config(config)
+            .ok()
+            .unwrap();
+
+        min.queue_frame(0, &[0x01], 1).unwrap();
+        assert_eq!(Err(min::TransportError::FifoFull(0)), min.queue_frame(0, &[0x02], 1));
+        assert!(min.ping().is_err());
+        assert_eq!(2, min.get_fifo_overflow_drop_cnt());
+        assert_eq!(1, min.health_check().queued_frames);
+
+        // The frames beyond the capacity of a resumed session are dropped too
+        let state = min::TransportState { sn_min: 0, sn_max: 0, rn: 0 };
+        let pending: Vec<(u8, Vec<u8>)> = (0..40).map(|i| (0, vec![i])).collect();
+        let min = min::Context::resume(String::from("min"), &uart, 0, state, pending).ok().unwrap();
+        assert_eq!(9, min.get_fifo_overflow_drop_cnt());
+        assert_eq!(31, min.health_check().queued_frames);
+
+        // A payload too long is rejected rather than restored truncated
+        let pending = vec![(0, vec![0x01]), (1, vec![0; 256])];
+        assert_eq!(
+            Some(min::TransportError::PayloadTooLong(256)),
+            min::Context::resume(String::from("min"), &uart, 0, state, pending).err()
+        );
+    }
+
+    #[test]
+    fn send_frame_ring() {
+        let uart = Uart::new(128, true);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            false,
+        );
+
+        uart.open();
+
+        let mut ring = [0_u8; 16];
+        for (i, byte) in ring.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        // Wraps around the end of the ring
+        assert_eq!(5, min.send_frame_ring(0x21, &ring, 14, 0x0f, 5).ok().unwrap());
+        let sent = uart.rx_buf.borrow()[0..uart.get_rx_data_len() as usize].to_vec();
+        assert_eq!(min.encode_frame(0x21, &[14, 15, 0, 1, 2]).ok().unwrap(), sent);
+
+        min.receive(&sent);
+        let msgs: Vec<min::Msg> = min.drain_msgs().collect();
+        assert_eq!(1, msgs.len());
+        assert_eq!(vec![14, 15, 0, 1, 2], msgs[0].buf);
+
+        uart.close();
+    }
+
+    #[test]
+    fn transport_tx_space_below_overhead() {
+        let uart1 = Uart::new(128, true);
+        // Less than a frame without payload
+        let uart2 = Uart::new(5, true);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+
+        uart1.open();
+        uart2.open();
+
+        // Not even partially written
+        assert_eq!(0, min2.reset_transport(true).unwrap());
+        assert_eq!(0, uart2.tx_frames.get());
+        assert_eq!(0, uart2.get_rx_data_len());
+        assert_eq!(1, min2.get_reset_send_failure_cnt());
+
+        // The frame is delivered but can't be acknowledged
+        min1.queue_frame(0x01, &[0x01], 1).unwrap();
+        min1.tick();
+        min2.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+        assert_eq!(1, min2.drain_msgs().count());
+        assert_eq!(0, uart2.tx_frames.get());
+        assert_eq!(1, min2.get_ack_send_failure_cnt());
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn transport_queue_frames() {
+        let uart = Uart::new(128, false);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+
+        let batch: Vec<(u8, &[u8])> = vec![(1, &[0x01][..]), (2, &[0x02, 0x02][..]), (3, &[][..])];
+        for _ in 0..9 {
+            assert_eq!(Ok(3), min.queue_frames(&batch));
+        }
+        assert_eq!(27, min.health_check().queued_frames);
+
+        // 4 frames left in the FIFO: the batch of 6 is rejected as a whole
+        let big_batch: Vec<(u8, &[u8])> = batch.iter().chain(batch.iter()).cloned().collect();
+        assert_eq!(Err(min::TransportError::FifoFull(4)), min.queue_frames(&big_batch));
+        assert_eq!(27, min.health_check().queued_frames);
+        assert_eq!(Ok(3), min.queue_frames(&batch));
+        assert_eq!(Err(min::TransportError::FifoFull(1)), min.queue_frames(&batch));
+        assert_eq!(30, min.health_check().queued_frames);
+
+        let too_long = [0_u8; 256];
+        assert_eq!(Err(min::TransportError::PayloadTooLong(256)), min.queue_frames(&[(0, &too_long[..])]));
+
+        min.disable_transport();
+        assert_eq!(Err(min::TransportError::NotSupported), min.queue_frames(&batch));
+    }
+
+    #[test]
+    fn transport_msg_is_transport() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, false);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+
+        uart1.open();
+        uart2.open();
+
+        min1.queue_frame(0x01, &[0x01], 1).unwrap();
+        min1.tick();
+        min2.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+        min1.send(0x02, [0x02]).ok().unwrap();
+        min2.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+
+        let msgs: Vec<min::Msg> = min2.drain_msgs().collect();
+        assert_eq!(2, msgs.len());
+        assert_eq!(0x01, msgs[0].min_id);
+        assert!(msgs[0].is_transport);
+        assert_eq!(0x02, msgs[1].min_id);
+        assert!(!msgs[1].is_transport);
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn transport_datagram() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, true);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+        min1.set_datagram_mode(true);
+        min2.set_datagram_mode(true);
+
+        uart1.open();
+        uart2.open();
+
+        // The buffer of each interface holds the last datagram sent
+        let payload = [0xaa, 0xaa, 0xaa, 0x01];
+        assert_eq!(11, min1.frame_wire_len(0x01, &payload));
+        min1.queue_frame(0x01, &payload, 4).unwrap();
+        min1.tick();
+        let datagram = uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize].to_vec();
+        // ID/control, sequence, length, payload not stuffed, checksum
+        assert_eq!(11, datagram.len());
+        assert_eq!(&[0x81, 0x00, 0x04, 0xaa, 0xaa, 0xaa, 0x01], &datagram[..7]);
+
+        min2.receive_datagram(&datagram);
+        let msgs: Vec<min::Msg> = min2.drain_msgs().collect();
+        assert_eq!(1, msgs.len());
+        assert_eq!(payload.to_vec(), msgs[0].buf);
+
+        // The ACK is a datagram too
+        min1.receive_datagram(&uart2.rx_buf.borrow()[0..uart2.get_rx_data_len() as usize]);
+        assert_eq!(vec![(0x01, 0)], min1.drain_acked().collect::<Vec<(u8, u8)>>());
+
+        // Truncated, padded and corrupted datagrams are dropped
+        min1.queue_frame(0x02, &[0x02], 1).unwrap();
+        min1.tick();
+        let datagram = uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize].to_vec();
+        min2.receive_datagram(&datagram[..datagram.len() - 1]);
+        let mut padded = datagram.clone();
+        padded.push(0x00);
+        min2.receive_datagram(&padded);
+        let mut corrupted = datagram.clone();
+        corrupted[3] ^= 0x01;
+        min2.receive_datagram(&corrupted);
+        assert_eq!(0, min2.drain_msgs().count());
+        let errors: Vec<min::FrameError> = min2.drain_errors().collect();
+        assert_eq!(3, errors.len());
+        assert_eq!(min::FrameError::Length { claimed: 1 }, errors[0]);
+        assert_eq!(min::FrameError::Length { claimed: 1 }, errors[1]);
+        assert!(matches!(errors[2], min::FrameError::Crc { .. }));
+
+        min2.receive_datagram(&datagram);
+        assert_eq!(1, min2.drain_msgs().count());
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn transport_frame_too_large_for_tx_buffer() {
+        let uart = Uart::new(32, false);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+        let payload = [0_u8; 32];
+
+        // Queued, but never sent
+        assert_eq!(Ok(()), min.queue_frame(0, &payload, 32));
+        assert_eq!(0, min.tick().frames_sent);
+        min.reset_transport(false).unwrap();
+
+        min.set_reject_frames_over_tx_space(true);
+        assert_eq!(Err(min::TransportError::FrameTooLargeForTxBuffer(43)), min.queue_frame(0, &payload, 32));
+        assert_eq!(Ok(()), min.queue_frame(0, &payload, 21));
+        assert_eq!(Err(min::TransportError::FrameTooLargeForTxBuffer(43)), min.queue_frames(&[(0, &payload[..1]), (0, &payload[..])]));
+        assert_eq!(1, min.health_check().queued_frames);
+        assert_eq!(1, min.tick().frames_sent);
+    }
+
+    #[test]
+    fn transport_restore_window_in_flight() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, true);
+        // Frames in flight without their payloads, see `TransportState`
+        let mut min1 = min::Context::from_transport_state(
+            String::from("min1"),
+            &uart1,
+            0,
+            min::TransportState { sn_min: 3, sn_max: 6, rn: 0 },
+        );
+        let mut min2 = min::Context::from_transport_state(
+            String::from("min2"),
+            &uart2,
+            0,
+            min::TransportState { sn_min: 0, sn_max: 0, rn: 3 },
+        );
+        assert_eq!(min::TransportState { sn_min: 3, sn_max: 3, rn: 0 }, min1.get_transport_state());
+
+        uart1.open();
+        uart2.open();
+
+        for i in 0..2 {
+            min1.queue_frame(0, &[i], 1).unwrap();
+            min1.tick();
+            min2.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+            min1.receive(&uart2.rx_buf.borrow()[0..uart2.get_rx_data_len() as usize]);
+        }
+        assert_eq!(vec![(0, 3), (0, 4)], min1.drain_acked().collect::<Vec<_>>());
+        assert_eq!(min::TransportState { sn_min: 5, sn_max: 5, rn: 0 }, min1.get_transport_state());
+        assert_eq!(2, min2.drain_msgs().count());
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn transport_accept_filter() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, true);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+        min2.set_accept_filter(|id, _port, _payload| id != 0x10);
+
+        uart1.open();
+        uart2.open();
+
+        min1.queue_frame(0x10, &[0x01], 1).unwrap();
+        min1.queue_frame(0x11, &[0x02], 1).unwrap();
+        for _ in 0..2 {
+            min1.tick();
+            min2.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+            min1.receive(&uart2.rx_buf.borrow()[0..uart2.get_rx_data_len() as usize]);
+        }
+
+        let ids: Vec<u8> = min2.drain_msgs().map(|msg| msg.min_id).collect();
+        assert_eq!(vec![0x11], ids);
+        assert_eq!(1, min2.get_filtered_drop_cnt());
+        // Both frames are acknowledged
+        assert_eq!(0, min1.health_check().queued_frames);
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn transport_coalesce_acks() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, true);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+
+        uart1.open();
+        uart2.open();
+
+        let mut received = Vec::new();
+        for i in 0..3 {
+            min1.queue_frame(0, &[i], 1).unwrap();
+            min1.tick();
+            received.extend_from_slice(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+        }
+        // Three frames in one poll, a single ACK
+        min2.poll(&received, received.len() as u32);
+        assert_eq!(1, uart2.tx_frames.get());
+        assert_eq!(3, min2.drain_msgs().count());
+
+        // It acknowledges them all
+        min1.receive(&uart2.rx_buf.borrow()[0..uart2.get_rx_data_len() as usize]);
+        assert_eq!(0, min1.health_check().queued_frames);
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn seq_comparisons() {
+        assert_eq!(1, min::seq_distance(0xff, 0x00));
+        assert_eq!(0xff, min::seq_distance(0x00, 0xff));
+
+        assert!(min::seq_lt(0x01, 0x02));
+        assert!(!min::seq_lt(0x02, 0x01));
+        assert!(!min::seq_lt(0x05, 0x05));
+        // Across the wrap
+        assert!(min::seq_lt(0xfe, 0x01));
+        assert!(!min::seq_lt(0x01, 0xfe));
+        assert!(min::seq_lt(0x00, 0x7f));
+        assert!(!min::seq_lt(0x00, 0x80));
+
+        assert!(min::seq_in_window(0x10, 0x10, 0x20));
+        assert!(min::seq_in_window(0x20, 0x10, 0x20));
+        assert!(!min::seq_in_window(0x21, 0x10, 0x20));
+        assert!(!min::seq_in_window(0x0f, 0x10, 0x20));
+        // Across the wrap
+        assert!(min::seq_in_window(0xff, 0xfc, 0x03));
+        assert!(min::seq_in_window(0x00, 0xfc, 0x03));
+        assert!(!min::seq_in_window(0x04, 0xfc, 0x03));
+        assert!(!min::seq_in_window(0xfb, 0xfc, 0x03));
+        // Empty window
+        assert!(min::seq_in_window(0x80, 0x80, 0x80));
+        assert!(!min::seq_in_window(0x81, 0x80, 0x80));
+    }
+
+    #[test]
+    fn transport_retransmit_reasons() {
+        let uart = Uart::new(128, true);
+        let clock = min::MockClock::new(0);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+        min.set_clock(&clock);
+
+        uart.open();
+
+        min.queue_frame(0, &[0x01], 1).unwrap();
+        min.queue_frame(0, &[0x02], 1).unwrap();
+        min.tick();
+        min.tick();
+        assert_eq!(2, uart.tx_frames.get());
+
+        // ACK of Rn 0 asking for 2 frames again (NACK)
+        let mut nack = vec![0xaa, 0xaa, 0xaa, 0xff, 0x00, 0x01, 0x02];
+        let crc = min::crc32(&nack[3..]);
+        nack.extend_from_slice(&crc.to_be_bytes());
+        nack.push(0x55);
+        min.receive(&nack);
+        assert_eq!(4, uart.tx_frames.get());
+        assert_eq!(2, min.get_retransmit_nack_cnt());
+        assert_eq!(0, min.get_retransmit_timeout_cnt());
+
+        // No ACK in time
+        clock.advance(1);
+        assert!(first_retransmit_ms(&mut min, &clock, 2000).is_some());
+        assert_eq!(2, min.get_retransmit_nack_cnt());
+        assert_eq!(1, min.get_retransmit_timeout_cnt());
+        assert_eq!(1, min.get_transport_stats().retransmits_timeout);
+
+        uart.close();
+    }
+
+    #[test]
+    fn transport_adaptive_retransmit() {
+        let uart = Uart::new(128, false);
+        let clock = min::MockClock::new(0);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+        min.set_clock(&clock);
+        min.set_adaptive_retransmit(Some((50, 3000)));
+        assert_eq!(None, min.srtt_ms());
+        assert_eq!(1000, min.rto_ms());
+
+        // Every frame acknowledged 100 ms after being sent
+        for seq in 0..20_u8 {
+            min.queue_frame(0, &[seq], 1).unwrap();
+            min.tick();
+            clock.advance(100);
+            let mut ack = vec![0xaa, 0xaa, 0xaa, 0xff, seq + 1, 0x01, seq + 1];
+            let crc = min::crc32(&ack[3..]);
+            ack.extend_from_slice(&crc.to_be_bytes());
+            ack.push(0x55);
+            min.receive(&ack);
+            if seq == 0 {
+                // SRTT + 4 * SRTT / 2
+                assert_eq!(300, min.rto_ms());
+            }
+        }
+        assert_eq!(Some(100), min.srtt_ms());
+        assert_eq!(100, min.rto_ms());
+        assert_eq!(0, min.get_transport_stats().retransmits_timeout);
+
+        // The retransmit follows the estimate
+        min.queue_frame(0, &[0x01], 1).unwrap();
+        min.tick();
+        clock.advance(1);
+        assert_eq!(Some(2100), first_retransmit_ms(&mut min, &clock, 3000));
+
+        // Clamped to the bounds
+        min.set_adaptive_retransmit(Some((500, 3000)));
+        assert_eq!(500, min.rto_ms());
+        min.set_adaptive_retransmit(None);
+        assert_eq!(1000, min.rto_ms());
+    }
+
+    // In-memory sink whose content stays readable after being handed over
+    struct SharedSink(Rc<RefCell<Vec<u8>>>);
+
+    impl std::io::Write for SharedSink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn transport_route_to_sink() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, true);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+        let streamed = Rc::new(RefCell::new(Vec::new()));
+        min2.route_to_sink(0x10, SharedSink(streamed.clone()));
+
+        uart1.open();
+        uart2.open();
+
+        min1.queue_frame(0x10, &[0x01, 0x02], 2).unwrap();
+        min1.queue_frame(0x11, &[0xff], 1).unwrap();
+        min1.queue_frame(0x10, &[0x03], 1).unwrap();
+        min1.queue_frame(0x10, &[0x04, 0x05], 2).unwrap();
+        for _ in 0..4 {
+            min1.tick();
+            min2.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+            min1.receive(&uart2.rx_buf.borrow()[0..uart2.get_rx_data_len() as usize]);
+        }
+
+        assert_eq!(vec![0x01, 0x02, 0x03, 0x04, 0x05], *streamed.borrow());
+        // Only the other IDs are queued
+        let msgs = min2.drain_msgs().collect::<Vec<min::Msg>>();
+        assert_eq!(1, msgs.len());
+        assert_eq!(0x11, msgs[0].min_id);
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn transport_shutdown() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, true);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+
+        uart1.open();
+        uart2.open();
+
+        for i in 0..3 {
+            min1.queue_frame(0, &[i], 1).unwrap();
+        }
+        // Each frame sent is passed to min2, its ACK is read back
+        let mut frames_seen = 0;
+        let read = |buf: &mut [u8]| {
+            if uart1.tx_frames.get() == frames_seen {
+                return 0;
+            }
+            frames_seen = uart1.tx_frames.get();
+            min2.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+            let len = uart2.get_rx_data_len() as usize;
+            buf[..len].copy_from_slice(&uart2.rx_buf.borrow()[..len]);
+            len
+        };
+        assert_eq!(Ok(()), min1.shutdown(read, 1000));
+
+        // The frames, then the RESET
+        assert_eq!(vec![vec![0], vec![1], vec![2]], min2.drain_msgs().map(|msg| msg.buf).collect::<Vec<Vec<u8>>>());
+        assert_eq!(0, min2.get_reset_cnt());
+        min2.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+        assert_eq!(1, min2.get_reset_cnt());
+        assert_eq!(0, min1.health_check().queued_frames);
+
+        // Nobody answers
+        min1.queue_frame(0, &[3], 1).unwrap();
+        assert_eq!(Err(min::TransportError::Timeout(1)), min1.shutdown(|_: &mut [u8]| 0, 20));
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn describe() {
+        let uart = Uart::new(128, false);
+        let mut min = min::Context::new(
+            String::from("link0"),
+            &uart,
+            3,
+            true,
+        );
+        min.set_header_crc(true);
+        let description = min.describe();
+        for field in ["name=link0", "port=3", "transport=on", "window_size=16", "retransmit_timeout_ms=1000",
+            "max_payload=255", "CRC-32", "header CRC-8", "header 0xaa 0xaa 0xaa"] {
+            assert!(description.contains(field), "{} missing", field);
+        }
+
+        min.disable_transport();
+        min.set_datagram_mode(true);
+        let description = min.describe();
+        assert!(description.contains("transport=off"));
+        assert!(!description.contains("window_size"));
+        assert!(description.contains("framing=datagram"));
+    }
+
+    #[test]
+    fn transport_pause_tx() {
+        let uart = Uart::new(128, false);
+        let clock = min::MockClock::new(0);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+        min.set_clock(&clock);
+
+        uart.open();
+
+        min.queue_frame(0, &[0x00], 1).unwrap();
+        min.tick();
+        assert_eq!(1, uart.tx_frames.get());
+
+        clock.advance(400);
+        min.pause_tx();
+        assert!(min.tx_paused());
+        min.queue_frame(1, &[0x01], 1).unwrap();
+        min.queue_frame(2, &[0x02], 1).unwrap();
+        assert!(min.send_frame(3, &[0x03], 1).is_err());
+        for _ in 0..10 {
+            clock.advance(100);
+            assert_eq!(min::TickReport::default(), min.tick());
+        }
+        assert_eq!(1, uart.tx_frames.get());
+
+        // The queued frames go out, the first one is not due again before 1000 ms of sending time
+        min.resume_tx();
+        assert!(!min.tx_paused());
+        for _ in 0..3 {
+            min.tick();
+        }
+        assert_eq!(3, uart.tx_frames.get());
+        clock.advance(400);
+        min.tick();
+        assert_eq!(3, uart.tx_frames.get());
+        assert_eq!(0, min.get_transport_stats().retransmits_timeout);
+
+        uart.close();
+    }
+
+    #[test]
+    fn transport_nack_on_crc_error() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, true);
+        let clock = min::MockClock::new(0);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+        min1.set_clock(&clock);
+        min2.set_clock(&clock);
+        min2.set_nack_on_crc_error(true);
+
+        uart1.open();
+        uart2.open();
+
+        min1.queue_frame(0, &[0x01], 1).unwrap();
+        min1.tick();
+        let mut corrupted = uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize].to_vec();
+        corrupted[6] ^= 0x02;
+        clock.advance(10);
+        min2.receive(&corrupted);
+        assert_eq!(1, min2.get_crc_error_cnt());
+        assert_eq!(1, min2.get_nack_sent_cnt());
+        assert!(min2.get_msg().is_err());
+
+        // Sent again on the NACK, long before the retransmit timeout
+        min1.receive(&uart2.rx_buf.borrow()[0..uart2.get_rx_data_len() as usize]);
+        assert_eq!(2, uart1.tx_frames.get());
+        assert_eq!(1, min1.get_retransmit_nack_cnt());
+        min2.receive(&uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize]);
+        assert_eq!(vec![0x01], min2.get_msg().ok().unwrap().buf);
+
+        // A NACK asks for nothing once the frame is acknowledged
+        min1.receive(&uart2.rx_buf.borrow()[0..uart2.get_rx_data_len() as usize]);
+        min2.receive(&corrupted);
+        assert_eq!(2, min2.get_nack_sent_cnt());
+        min1.receive(&uart2.rx_buf.borrow()[0..uart2.get_rx_data_len() as usize]);
+        assert_eq!(2, uart1.tx_frames.get());
+        assert_eq!(1, min1.get_retransmit_nack_cnt());
+        assert_eq!(0, min1.get_transport_stats().retransmits_timeout);
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn transport_nack_not_sent() {
+        let uart1 = Uart::new(128, true);
+        let uart2 = Uart::new(128, true);
+        let mut min1 = min::Context::new(
+            String::from("min1"),
+            &uart1,
+            0,
+            true,
+        );
+        let mut min2 = min::Context::new(
+            String::from("min2"),
+            &uart2,
+            0,
+            true,
+        );
+        min2.set_nack_on_crc_error(true);
+
+        uart1.open();
+        uart2.open();
+
+        min1.queue_frame(0, &[0x01], 1).unwrap();
+        min1.tick();
+        let mut corrupted = uart1.rx_buf.borrow()[0..uart1.get_rx_data_len() as usize].to_vec();
+        corrupted[6] ^= 0x02;
+        min2.receive(&corrupted);
+        let nack = uart2.rx_buf.borrow()[0..uart2.get_rx_data_len() as usize].to_vec();
+
+        // Nothing goes out while paused, nothing is counted
+        min1.pause_tx();
+        min1.receive(&nack);
+        assert_eq!(1, uart1.tx_frames.get());
+        assert_eq!(Some(0), min1.oldest_unacked_attempts());
+        assert_eq!(0, min1.get_retransmit_nack_cnt());
+
+        min1.resume_tx();
+        min1.receive(&nack);
+        assert_eq!(2, uart1.tx_frames.get());
+        assert_eq!(Some(1), min1.oldest_unacked_attempts());
+        assert_eq!(1, min1.get_retransmit_nack_cnt());
+
+        uart1.close();
+        uart2.close();
+    }
+
+    #[test]
+    fn transport_new_frame_not_sent() {
+        let uart = Uart::new(128, false);
+        let clock = min::MockClock::new(1000);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+        min.set_clock(&clock);
+
+        uart.open();
+
+        // No tx space: the frame stays out of the window
+        min.queue_frame(0, &[0x01], 1).unwrap();
+        min.set_tx_space_override(Some(0));
+        assert_eq!(min::TickReport::default(), min.tick());
+        assert_eq!(min::TransportState { sn_min: 0, sn_max: 0, rn: 0 }, min.get_transport_state());
+        assert_eq!(None, min.oldest_unacked_attempts());
+
+        // Sent as a new frame, not as a retransmission
+        clock.advance(400);
+        min.set_tx_space_override(None);
+        assert_eq!(min::TickReport { acks_sent: 0, frames_sent: 1, frames_retransmitted: 0 }, min.tick());
+        assert_eq!(Some(0), min.oldest_unacked_attempts());
+        assert_eq!(0, min.get_transport_stats().retransmits_timeout);
+        assert_eq!(1, uart.tx_frames.get());
+
+        uart.close();
+    }
+
+    #[test]
+    fn transport_oldest_unacked_attempts() {
+        let uart = Uart::new(128, true);
+        let clock = min::MockClock::new(0);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+        min.set_clock(&clock);
+        // The dead peer still counts as connected, so that the frames keep being sent again
+        min.set_timeouts(50, 100, 1_000_000).ok().unwrap();
+
+        uart.open();
+
+        assert_eq!(None, min.oldest_unacked_attempts());
+        min.queue_frame(3, &[0x01], 1).unwrap();
+        min.queue_frame(4, &[0x02], 1).unwrap();
+        assert_eq!(None, min.oldest_unacked());
+        min.tick();
+        min.tick();
+        assert_eq!(Some(0), min.oldest_unacked_attempts());
+        let oldest = min.oldest_unacked().unwrap();
+        assert_eq!((3, 0), (oldest.min_id, oldest.seq));
+
+        for attempts in 1..=3 {
+            // Both frames are sent again in turn
+            clock.advance(100);
+            min.tick();
+            clock.advance(1);
+            min.tick();
+            assert_eq!(Some(attempts), min.oldest_unacked_attempts());
+        }
+        let oldest = min.oldest_unacked().unwrap();
+        assert_eq!((3, 0, 3), (oldest.min_id, oldest.seq, oldest.retransmits));
+
+        uart.close();
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn transport_inject_stale_ack() {
+        let uart = Uart::new(128, false);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+        for i in 0..2 {
+            min.queue_frame(0x01, &[i], 1).unwrap();
+            min.tick();
+        }
+        assert_eq!(2, min.health_check().in_flight);
+
+        // An ACK beyond the frames sent, e.g. from an old session, acknowledges nothing
+        min.inject_frame(0xff, 5, &[5]).unwrap();
+        assert_eq!(2, min.health_check().in_flight);
+
+        min.inject_frame(0xff, 2, &[2]).unwrap();
+        assert_eq!(0, min.health_check().queued_frames);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn transport_inject_out_of_sequence() {
+        let uart = Uart::new(128, false);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+
+        // Ahead of Rn: the frame before it got lost, dropped
+        min.inject_frame(0x80 | 0x05, 1, &[0x02]).unwrap();
+        assert!(min.get_msg().is_err());
+        assert_eq!(vec![min::FrameError::SequenceMismatch { seq: 1, rn: 0 }], min.drain_errors().collect::<Vec<min::FrameError>>());
+
+        // Both delivered once received in sequence
+        min.inject_frame(0x80 | 0x05, 0, &[0x01]).unwrap();
+        min.inject_frame(0x80 | 0x05, 1, &[0x02]).unwrap();
+        let payloads: Vec<Vec<u8>> = min.drain_msgs().map(|msg| msg.buf).collect();
+        assert_eq!(vec![vec![0x01], vec![0x02]], payloads);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn transport_inject_reset() {
+        let uart = Uart::new(128, false);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+        for i in 0..3 {
+            min.queue_frame(0x01, &[i], 1).unwrap();
+        }
+        min.tick();
+
+        // The FIFO is emptied, sent or not
+        min.inject_frame(0xfe, 0, &[]).unwrap();
+        assert_eq!(1, min.get_reset_cnt());
+        assert_eq!(0, min.health_check().queued_frames);
+        assert_eq!(0, min.health_check().in_flight);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn transport_inject_too_long() {
+        let uart = Uart::new(128, false);
+        let mut min = min::Context::new(
+            String::from("min"),
+            &uart,
+            0,
+            true,
+        );
+
+        assert_eq!(Err(min::TransportError::PayloadTooLong(256)), min.inject_frame(0x80 | 0x05, 0, &[0; 256]));
+        assert!(min.get_msg().is_err());
+        // Rn did not move
+        min.inject_frame(0x80 | 0x05, 0, &[0x01]).unwrap();
+        assert_eq!(vec![0x01], min.get_msg().ok().unwrap().buf);
+    }
 }